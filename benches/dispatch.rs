@@ -0,0 +1,116 @@
+// fib_10_recursive workload of the assembler tests, dominated by guest calls
+//
+//     cargo bench --bench dispatch
+use gomoku::assembler::assemble;
+use gomoku::collector::{Address, Collector, CollectorId, Owned, Shared};
+use gomoku::interpreter::Interpreter;
+use gomoku::native::NativeRegistry;
+use gomoku::objects::{Dispatch, Integer};
+use gomoku::runner::CollectorInterface;
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+struct TaskCollector<'a>(&'a Collector);
+impl CollectorInterface for TaskCollector<'_> {
+    fn allocate(&mut self, owned: Owned) -> Address {
+        self.0.allocate(0, owned)
+    }
+    fn inspect(&self, address: Address) -> Shared {
+        self.0.inspect(0, address)
+    }
+    fn replace(&mut self, address: Address, owned: Owned) -> Owned {
+        self.0.replace_owned(address, owned)
+    }
+    // constants are cached across runs, so they outlive the task heap
+    fn allocate_constant(&mut self, owned: Owned) -> Address {
+        self.0.allocate_constant(owned)
+    }
+    fn collector_id(&self) -> CollectorId {
+        self.0.id()
+    }
+}
+
+fn registry() -> NativeRegistry {
+    let mut registry = NativeRegistry::standard();
+    registry.register("assert_55", 0, |context| {
+        let int = context.inspect(context.get_argument(0));
+        assert_eq!(int.as_ref().downcast_ref(), Some(&Integer(55)));
+    });
+    registry
+}
+
+const SOURCE: &str = "
+    .module main
+    .constant integer 10
+    .constant dispatch main fib
+    .constant integer 1
+    .constant integer 2
+    .constant integer -1
+    .constant integer -2
+    .symbol start
+        load_constant 0
+        load_constant 1
+        call 1
+        assert_floating 1
+        operate 1 assert_55
+        return 0
+
+    .symbol fib
+        assert_floating 1
+        load_constant 2
+        operate 2 integer.eq
+        jump base
+        copy 3
+        load_constant 3
+        operate 2 integer.eq
+        jump base
+        load_constant 4
+        copy 4
+        operate 2 integer.add
+        load_constant 1
+        call 1
+        assert_floating 1
+        load_constant 5
+        copy 3
+        operate 2 integer.add
+        load_constant 1
+        call 1
+        assert_floating 1
+        copy 4
+        operate 2 integer.add
+        return 1
+    base:
+        load_constant 2
+        return 1
+";
+
+fn main() {
+    let mut interp = Interpreter::with_registry(registry());
+    interp.load_module(assemble(SOURCE).unwrap()).unwrap();
+    let start = Dispatch {
+        module_id: "main".into(),
+        symbol: "start".into(),
+    };
+
+    let collector = Collector::new();
+    let n_iteration = 2000;
+    let mut best = Duration::MAX;
+    for _ in 0..20 {
+        let timer = Instant::now();
+        for _ in 0..n_iteration {
+            collector.spawn(0);
+            interp.push_call(start, 0).unwrap();
+            while interp.has_step() {
+                interp.step(&mut TaskCollector(&collector)).unwrap();
+            }
+            black_box(interp.reset());
+            collector.join(0);
+            collector.epoch_change(Default::default);
+        }
+        best = best.min(timer.elapsed());
+    }
+    println!(
+        "fib_10_recursive: {:?} per run (best of 20 x {n_iteration})",
+        best / n_iteration
+    );
+}
@@ -0,0 +1,490 @@
+// textual assembly for `Module`
+//
+//     .module main
+//     .import lib double      ; module and symbol called from this module
+//     .export start           ; symbol callable from other modules
+//     .constant dispatch lib double ; next entry of constant pool, also `true` and
+//                             ; `false`, pushed with `load_constant <index>`
+//     .symbol start           ; symbol entry, also usable as a label
+//     .symbol fib 1 1         ; with parameter and result counts checked on call
+//                             ; and return
+//         operate 0 push_ten  ; native id, resolved on loading
+//     loop:                   ; label
+//         jump loop           ; jump target by label or raw offset, also for
+//                             ; goto, jump_if, jump_unless and try
+//         return 0
+//
+// one instruction or directive per line, `;` starts a comment
+use crate::interpreter::{ByteCode, Constant, Module, Signature};
+use crate::name::Name;
+use crate::objects::Dispatch;
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+use std::str::FromStr;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AssembleError {
+    pub line: usize,
+    pub kind: AssembleErrorKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AssembleErrorKind {
+    MissingModule,
+    DuplicateModule,
+    UnknownDirective(String),
+    UnknownMnemonic(String),
+    MissingOperand,
+    UnexpectedOperand(String),
+    InvalidOperand(String),
+    DuplicateLabel(String),
+    UnknownLabel(String),
+    JumpOutOfRange(String),
+}
+
+impl Display for AssembleError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: ", self.line)?;
+        match &self.kind {
+            AssembleErrorKind::MissingModule => write!(f, "missing .module directive"),
+            AssembleErrorKind::DuplicateModule => write!(f, "duplicated .module directive"),
+            AssembleErrorKind::UnknownDirective(name) => write!(f, "unknown directive {name}"),
+            AssembleErrorKind::UnknownMnemonic(name) => write!(f, "unknown instruction {name}"),
+            AssembleErrorKind::MissingOperand => write!(f, "missing operand"),
+            AssembleErrorKind::UnexpectedOperand(token) => write!(f, "unexpected operand {token}"),
+            AssembleErrorKind::InvalidOperand(token) => write!(f, "invalid operand {token}"),
+            AssembleErrorKind::DuplicateLabel(name) => write!(f, "duplicated label {name}"),
+            AssembleErrorKind::UnknownLabel(name) => write!(f, "unknown label {name}"),
+            AssembleErrorKind::JumpOutOfRange(name) => write!(f, "jump to {name} out of range"),
+        }
+    }
+}
+
+impl Error for AssembleError {}
+
+pub fn assemble(source: &str) -> Result<Module, AssembleError> {
+    let mut id = None;
+    let mut program = Vec::new();
+    let mut symbol_table = HashMap::new();
+    let mut signature_table = HashMap::new();
+    let mut label_table = HashMap::new();
+    let mut import_list = Vec::new();
+    let mut export_set = HashSet::new();
+    let mut constant_list = Vec::new();
+    // (instruction offset, line, target)
+    let mut jump_list = Vec::new();
+
+    for (index, line) in source.lines().enumerate() {
+        let line_number = index + 1;
+        let error = |kind| AssembleError {
+            line: line_number,
+            kind,
+        };
+        let line = line.split(';').next().unwrap();
+        let mut token_list = line.split_whitespace();
+        let Some(head) = token_list.next() else {
+            continue;
+        };
+
+        if let Some(label) = head.strip_suffix(':') {
+            if label_table
+                .insert(label.to_string(), program.len())
+                .is_some()
+            {
+                return Err(error(AssembleErrorKind::DuplicateLabel(label.to_string())));
+            }
+            expect_end(&mut token_list).map_err(error)?;
+            continue;
+        }
+
+        if let Some(directive) = head.strip_prefix('.') {
+            let name = expect_operand(&mut token_list).map_err(error)?;
+            match directive {
+                "module" => {
+                    if id.replace(Name::new(name)).is_some() {
+                        return Err(error(AssembleErrorKind::DuplicateModule));
+                    }
+                }
+                "symbol" => {
+                    if label_table
+                        .insert(name.to_string(), program.len())
+                        .is_some()
+                    {
+                        return Err(error(AssembleErrorKind::DuplicateLabel(name.to_string())));
+                    }
+                    symbol_table.insert(Name::new(name), program.len());
+                    let mut token_list = token_list.by_ref().peekable();
+                    if token_list.peek().is_some() {
+                        let n_parameter = parse_operand(&mut token_list).map_err(error)?;
+                        let n_result = parse_operand(&mut token_list).map_err(error)?;
+                        let signature = Signature {
+                            n_parameter,
+                            n_result,
+                        };
+                        signature_table.insert(Name::new(name), signature);
+                    }
+                }
+                "import" => {
+                    let symbol = expect_operand(&mut token_list).map_err(error)?;
+                    import_list.push((Name::new(name), Name::new(symbol)));
+                }
+                "export" => {
+                    export_set.insert(Name::new(name));
+                }
+                "constant" => constant_list.push(match name {
+                    "true" => Constant::True,
+                    "false" => Constant::False,
+                    "dispatch" => {
+                        let module_id = expect_operand(&mut token_list).map_err(error)?;
+                        let symbol = expect_operand(&mut token_list).map_err(error)?;
+                        Constant::Dispatch(Dispatch {
+                            module_id: Name::new(module_id),
+                            symbol: Name::new(symbol),
+                        })
+                    }
+                    "integer" => Constant::Integer(parse_operand(&mut token_list).map_err(error)?),
+                    "float" => Constant::Float(parse_operand(&mut token_list).map_err(error)?),
+                    _ => return Err(error(AssembleErrorKind::InvalidOperand(name.to_string()))),
+                }),
+                _ => {
+                    return Err(error(AssembleErrorKind::UnknownDirective(
+                        directive.to_string(),
+                    )))
+                }
+            }
+            expect_end(&mut token_list).map_err(error)?;
+            continue;
+        }
+
+        let instruction = match head {
+            "copy" => ByteCode::Copy(parse_operand(&mut token_list).map_err(error)?),
+            "operate" => {
+                let n_argument = parse_operand(&mut token_list).map_err(error)?;
+                let native_id = expect_operand(&mut token_list).map_err(error)?;
+                ByteCode::Operate(n_argument, native_id.to_string())
+            }
+            "jump" | "goto" | "jump_if" | "jump_unless" | "try" => {
+                let operand = expect_operand(&mut token_list).map_err(error)?;
+                jump_list.push((program.len(), line_number, operand.to_string()));
+                // placeholder, offset is patched after all labels are known
+                match head {
+                    "jump" => ByteCode::Jump(0),
+                    "goto" => ByteCode::Goto(0),
+                    "jump_if" => ByteCode::JumpIf(0),
+                    "jump_unless" => ByteCode::JumpUnless(0),
+                    _ => ByteCode::Try(0),
+                }
+            }
+            "call" => ByteCode::Call(parse_operand(&mut token_list).map_err(error)?),
+            "tail_call" => ByteCode::TailCall(parse_operand(&mut token_list).map_err(error)?),
+            "yield" => ByteCode::Yield(parse_operand(&mut token_list).map_err(error)?),
+            "resume" => ByteCode::Resume(parse_operand(&mut token_list).map_err(error)?),
+            "return" => ByteCode::Return(parse_operand(&mut token_list).map_err(error)?),
+            "assert_floating" => {
+                ByteCode::AssertFloating(parse_operand(&mut token_list).map_err(error)?)
+            }
+            "pack_floating" => {
+                ByteCode::PackFloating(parse_operand(&mut token_list).map_err(error)?)
+            }
+            "load_constant" => {
+                ByteCode::LoadConstant(parse_operand(&mut token_list).map_err(error)?)
+            }
+            "load_local" => ByteCode::LoadLocal(parse_operand(&mut token_list).map_err(error)?),
+            "store_local" => ByteCode::StoreLocal(parse_operand(&mut token_list).map_err(error)?),
+            "pop" => ByteCode::Pop,
+            "swap" => ByteCode::Swap,
+            "rotate" => match parse_operand(&mut token_list).map_err(error)? {
+                0 => return Err(error(AssembleErrorKind::InvalidOperand(String::from("0")))),
+                n => ByteCode::Rotate(n),
+            },
+            "unpack" => ByteCode::Unpack,
+            "end_try" => ByteCode::EndTry,
+            "throw" => ByteCode::Throw,
+            _ => return Err(error(AssembleErrorKind::UnknownMnemonic(head.to_string()))),
+        };
+        expect_end(&mut token_list).map_err(error)?;
+        program.push(instruction);
+    }
+
+    for (offset, line, operand) in jump_list {
+        let error = |kind| AssembleError { line, kind };
+        let relative = if operand.starts_with(['+', '-']) {
+            operand
+                .parse()
+                .map_err(|_| error(AssembleErrorKind::InvalidOperand(operand.clone())))?
+        } else {
+            let Some(&target) = label_table.get(&operand) else {
+                return Err(error(AssembleErrorKind::UnknownLabel(operand)));
+            };
+            // relative to the instruction next to the jump
+            target as isize - (offset + 1) as isize
+        };
+        let out_of_range = |_| error(AssembleErrorKind::JumpOutOfRange(operand.clone()));
+        program[offset] = match program[offset] {
+            ByteCode::Jump(_) => ByteCode::Jump(relative.try_into().map_err(out_of_range)?),
+            ByteCode::Goto(_) => ByteCode::Goto(relative.try_into().map_err(out_of_range)?),
+            ByteCode::JumpIf(_) => ByteCode::JumpIf(relative.try_into().map_err(out_of_range)?),
+            ByteCode::JumpUnless(_) => {
+                ByteCode::JumpUnless(relative.try_into().map_err(out_of_range)?)
+            }
+            _ => ByteCode::Try(relative.try_into().map_err(out_of_range)?),
+        };
+    }
+
+    Ok(Module {
+        id: id.ok_or(AssembleError {
+            line: source.lines().count(),
+            kind: AssembleErrorKind::MissingModule,
+        })?,
+        program,
+        symbol_table,
+        signature_table,
+        import_list,
+        export_set,
+        constant_list,
+    })
+}
+
+fn expect_operand<'a>(
+    token_list: &mut impl Iterator<Item = &'a str>,
+) -> Result<&'a str, AssembleErrorKind> {
+    token_list.next().ok_or(AssembleErrorKind::MissingOperand)
+}
+
+fn parse_operand<'a, T: FromStr>(
+    token_list: &mut impl Iterator<Item = &'a str>,
+) -> Result<T, AssembleErrorKind> {
+    let operand = expect_operand(token_list)?;
+    operand
+        .parse()
+        .map_err(|_| AssembleErrorKind::InvalidOperand(operand.to_string()))
+}
+
+fn expect_end<'a>(token_list: &mut impl Iterator<Item = &'a str>) -> Result<(), AssembleErrorKind> {
+    match token_list.next() {
+        Some(token) => Err(AssembleErrorKind::UnexpectedOperand(token.to_string())),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::collector::TestCollector;
+    use crate::interpreter::{Interpreter, LoadError};
+    use crate::native::NativeRegistry;
+    use crate::objects::{Dispatch, Integer};
+
+    fn registry() -> NativeRegistry {
+        let mut registry = NativeRegistry::standard();
+        registry.register("assert_55", 0, |context| {
+            let int = context.inspect(context.get_argument(0));
+            assert_eq!(int.as_ref().downcast_ref(), Some(&Integer(55)));
+        });
+        registry
+    }
+
+    #[test]
+    fn fib_10_recursive() {
+        let module = assemble(
+            "
+                .module main
+                .constant integer 10
+                .constant dispatch main fib
+                .constant integer 1
+                .constant integer 2
+                .constant integer -1
+                .constant integer -2
+                .symbol start
+                    load_constant 0
+                    load_constant 1
+                    call 1
+                    assert_floating 1
+                    operate 1 assert_55
+                    return 0
+
+                .symbol fib                 ; n
+                    assert_floating 1
+                    load_constant 2
+                    operate 2 integer.eq    ; ? 1 n
+                    jump base
+                    copy 3
+                    load_constant 3
+                    operate 2 integer.eq    ; ? 2 n
+                    jump base
+                    load_constant 4
+                    copy 4
+                    operate 2 integer.add   ; n' n
+                    load_constant 1
+                    call 1
+                    assert_floating 1
+                    load_constant 5
+                    copy 3
+                    operate 2 integer.add   ; n'' n -2 fib(n')
+                    load_constant 1
+                    call 1
+                    assert_floating 1
+                    copy 4
+                    operate 2 integer.add
+                    return 1
+                base:
+                    load_constant 2
+                    return 1
+                ",
+        )
+        .unwrap();
+        assert_eq!(module.symbol_table.get(&Name::new("fib")), Some(&6));
+        assert!(matches!(module.program[9], ByteCode::Jump(19)));
+        assert!(matches!(module.program[13], ByteCode::Jump(15)));
+
+        let mut interp = Interpreter::with_registry(registry());
+        interp.load_module(module).unwrap();
+        interp
+            .push_call(
+                Dispatch {
+                    module_id: Name::new("main"),
+                    symbol: Name::new("start"),
+                },
+                0,
+            )
+            .unwrap();
+        let mut collector = TestCollector::default();
+        while interp.has_step() {
+            interp.step(&mut collector).unwrap();
+        }
+    }
+
+    #[test]
+    fn fib_10_loop() {
+        let module = assemble(
+            "
+                .module main
+                .constant integer 0
+                .constant integer 1
+                .constant integer 10
+                .symbol start
+                    load_constant 1         ; b
+                    load_constant 0         ; a b
+                    load_constant 0         ; i a b
+                loop:
+                    load_constant 2
+                    copy 2
+                    operate 2 integer.eq     ; ? i 10 i a b
+                    jump_unless body
+                    goto done
+                body:
+                    copy 4
+                    copy 6
+                    operate 2 integer.add    ; a+b b a i 10 i a b
+                    load_constant 1
+                    copy 5
+                    operate 2 integer.add    ; i' i 1 a+b b a i 10 i a b
+                    copy 4
+                    copy 6
+                    copy 3                  ; i' b a+b ...
+                    goto loop
+                done:
+                    copy 4
+                    operate 1 assert_55
+                    return 0
+                ",
+        )
+        .unwrap();
+        assert!(matches!(module.program[7], ByteCode::Goto(10)));
+        assert!(matches!(module.program[17], ByteCode::Goto(-15)));
+
+        let mut interp = Interpreter::with_registry(registry());
+        interp.load_module(module).unwrap();
+        interp
+            .push_call(
+                Dispatch {
+                    module_id: Name::new("main"),
+                    symbol: Name::new("start"),
+                },
+                0,
+            )
+            .unwrap();
+        let mut collector = TestCollector::default();
+        while interp.has_step() {
+            interp.step(&mut collector).unwrap();
+        }
+    }
+
+    #[test]
+    fn backward_jump() {
+        let module = assemble(
+            "
+                .module main
+                .symbol start
+                top:
+                    jump top
+                    jump -2
+                    jump +0
+                    return 0
+                ",
+        )
+        .unwrap();
+        assert!(matches!(module.program[0], ByteCode::Jump(-1)));
+        assert!(matches!(module.program[1], ByteCode::Jump(-2)));
+        assert!(matches!(module.program[2], ByteCode::Jump(0)));
+    }
+
+    #[test]
+    fn reject_invalid_source() {
+        let reject = |source| assemble(source).unwrap_err();
+        assert_eq!(
+            reject(".symbol start\nreturn 0"),
+            AssembleError {
+                line: 2,
+                kind: AssembleErrorKind::MissingModule
+            }
+        );
+        assert_eq!(
+            reject(".module main\n  jump nowhere"),
+            AssembleError {
+                line: 2,
+                kind: AssembleErrorKind::UnknownLabel(String::from("nowhere"))
+            }
+        );
+        assert_eq!(
+            reject(".module main\n  return 0 1"),
+            AssembleError {
+                line: 2,
+                kind: AssembleErrorKind::UnexpectedOperand(String::from("1"))
+            }
+        );
+        assert_eq!(
+            reject(".module main\nend:\nend:"),
+            AssembleError {
+                line: 3,
+                kind: AssembleErrorKind::DuplicateLabel(String::from("end"))
+            }
+        );
+        assert_eq!(
+            reject(".module main\n  rotate 0"),
+            AssembleError {
+                line: 2,
+                kind: AssembleErrorKind::InvalidOperand(String::from("0"))
+            }
+        );
+        let unknown_native = assemble(".module main\n  operate 0 push_3").unwrap();
+        assert!(matches!(
+            Interpreter::with_registry(registry()).load_module(unknown_native),
+            Err(LoadError::UnknownNative { offset: 0, native_id, .. }) if native_id == "push_3"
+        ));
+        let far_away = format!(".module main\n jump end\n{}end:", "unpack\n".repeat(200));
+        assert_eq!(
+            reject(&far_away),
+            AssembleError {
+                line: 2,
+                kind: AssembleErrorKind::JumpOutOfRange(String::from("end"))
+            }
+        );
+        let far_away = far_away.replace("jump end", "goto end");
+        assert!(matches!(
+            assemble(&far_away).unwrap().program[0],
+            ByteCode::Goto(200)
+        ));
+    }
+}
@@ -7,7 +7,7 @@ impl Closure {
     pub fn operate_apply(context: &mut dyn OperateContext) {
         let closure = context.inspect(context.get_argument(0));
         let closure: &Closure = closure.as_ref().downcast_ref().unwrap();
-        let dispatch = closure.dispatch.clone();
+        let dispatch = closure.dispatch;
         let dispatch = context.allocate(dispatch.into());
         context.push_result(dispatch);
         let pack = List(closure.capture_list.clone());
@@ -30,19 +30,19 @@ impl Closure {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::collector::{Address, Owned, Shared};
-    use crate::interpreter::{ByteCode, Interpreter, Module, ModuleId, OperateContext};
-    use crate::objects::{Dispatch, LeafObject, Ready};
+    use crate::collector::TestCollector;
+    use crate::interpreter::{ByteCode, Interpreter, Module, ModuleId, Signature};
+    use crate::name::Name;
+    use crate::native::NativeRegistry;
+    use crate::objects::{Dispatch, Integer, Ready};
     use crate::runner::CollectorInterface;
     use crate::GeneralInterface;
-    use std::collections::HashMap;
-    use std::sync::{Arc, Mutex};
 
     fn main_module() -> ModuleId {
-        String::from("main")
+        Name::new("main")
     }
-    fn start_symbol() -> String {
-        String::from("start")
+    fn start_symbol() -> Name {
+        Name::new("start")
     }
     fn start_dispatch() -> Dispatch {
         Dispatch {
@@ -51,146 +51,131 @@ mod tests {
         }
     }
 
-    #[derive(Default)]
-    struct Collector {
-        allocate_number: u32,
-        storage: HashMap<Address, Arc<dyn GeneralInterface>>,
-    }
-    impl CollectorInterface for Collector {
-        fn allocate(&mut self, owned: Owned) -> Address {
-            self.allocate_number += 1;
-            let address = (0, self.allocate_number);
-            self.storage.insert(address, owned.into());
-            address
-        }
-        fn inspect(&self, address: Address) -> Shared {
-            self.storage.get(&address).unwrap().clone().into()
-        }
-        fn replace(&mut self, address: Address, owned: Owned) -> Owned {
-            self.storage.insert(address, owned.into()).unwrap().into()
-        }
-    }
-
-    fn push_literal<T: GeneralInterface + Clone>(literal: T) -> ByteCode {
-        ByteCode::Operate(
-            0,
-            Box::new(move |context| {
-                let literal = context.allocate(literal.clone().into());
-                context.push_result(literal);
-            }),
-        )
-    }
-
-    fn assert_top<T: GeneralInterface + Eq>(expect: T) -> ByteCode {
-        ByteCode::Operate(
-            1,
-            Box::new(move |context| {
-                let top = context.inspect(context.get_argument(0));
-                assert_eq!((*top).as_ref().downcast_ref(), Some(&expect));
-            }),
-        )
+    fn push_literal<T: GeneralInterface + Clone>(
+        registry: &mut NativeRegistry,
+        literal: T,
+    ) -> ByteCode {
+        let native_id = format!("push {literal:?}");
+        registry.register(native_id.clone(), 1, move |context| {
+            let literal = context.allocate(literal.clone().into());
+            context.push_result(literal);
+        });
+        ByteCode::Operate(0, native_id)
     }
 
-    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
-    struct I32(i32);
-    impl LeafObject for I32 {}
-    impl I32 {
-        fn operate_add_two(context: &mut dyn OperateContext) {
-            let int_a = context.inspect(context.get_argument(0));
-            let int_a: I32 = *(*int_a).as_ref().downcast_ref().unwrap();
-            let int_b = context.inspect(context.get_argument(1));
-            let int_b: I32 = *(*int_b).as_ref().downcast_ref().unwrap();
-            let int_c = context.allocate(I32(int_a.0 + int_b.0).into());
-            context.push_result(int_c);
-        }
+    fn assert_top<T: GeneralInterface + Eq>(registry: &mut NativeRegistry, expect: T) -> ByteCode {
+        let native_id = format!("assert {expect:?}");
+        registry.register(native_id.clone(), 0, move |context| {
+            let top = context.inspect(context.get_argument(0));
+            assert_eq!((*top).as_ref().downcast_ref(), Some(&expect));
+        });
+        ByteCode::Operate(1, native_id)
     }
 
     #[test]
     fn add_two_closure() {
-        let mut interp = Interpreter::new();
-        let closure_symbol = || String::from("(closure)");
-        interp.load_module(Module {
+        let mut registry = NativeRegistry::standard();
+        let closure_symbol = || Name::new("(closure)");
+        let module = Module {
             id: main_module(),
             symbol_table: [(start_symbol(), 0), (closure_symbol(), 18)]
                 .into_iter()
                 .collect(),
             program: vec![
                 // [add two]
-                push_literal(Closure {
-                    dispatch: Dispatch {
-                        module_id: main_module(),
-                        symbol: closure_symbol(),
+                push_literal(
+                    &mut registry,
+                    Closure {
+                        dispatch: Dispatch {
+                            module_id: main_module(),
+                            symbol: closure_symbol(),
+                        },
+                        capture_list: Vec::new(),
                     },
-                    capture_list: Vec::new(),
-                }),
+                ),
                 // 2 [add two]
-                push_literal(I32(2)),
+                push_literal(&mut registry, Integer(2)),
                 // [capture pack] [add two]
                 ByteCode::PackFloating(1),
                 // [capture pack] [add two]*
-                ByteCode::Operate(2, Box::new(Closure::operate_capture)),
+                ByteCode::Operate(2, "closure.capture".into()),
                 // [add two]
                 ByteCode::Copy(2),
                 // [capture pack] [dispatch] [add two]
-                ByteCode::Operate(1, Box::new(Closure::operate_apply)),
+                ByteCode::Operate(1, "closure.apply".into()),
                 // 1 [capture pack] [dispatch] [add two]
-                push_literal(I32(1)),
+                push_literal(&mut registry, Integer(1)),
                 // [dispatch] 1 [capture pack] | [dispatch] [add two]
                 ByteCode::Copy(3),
                 // [add two](1) | [dispatch] [add two]
                 ByteCode::Call(2),
                 ByteCode::AssertFloating(1),
-                assert_top(I32(3)),
+                assert_top(&mut registry, Integer(3)),
                 // [add two]
                 ByteCode::Copy(3),
-                ByteCode::Operate(1, Box::new(Closure::operate_apply)),
-                push_literal(I32(40)),
+                ByteCode::Operate(1, "closure.apply".into()),
+                push_literal(&mut registry, Integer(40)),
                 ByteCode::Copy(3),
                 ByteCode::Call(2),
-                assert_top(I32(42)),
+                assert_top(&mut registry, Integer(42)),
                 ByteCode::Return(0),
                 // (closure): variable [capture pack]
                 // [capture pack] variable
                 ByteCode::Copy(2),
                 ByteCode::Unpack,
                 ByteCode::AssertFloating(3),
-                ByteCode::Operate(2, Box::new(I32::operate_add_two)),
+                ByteCode::Operate(2, "integer.add".into()),
                 ByteCode::Return(1),
             ],
-        });
-        interp.push_call(start_dispatch(), 0);
-        let mut collector = Collector::default();
+            signature_table: [(
+                closure_symbol(),
+                Signature {
+                    n_parameter: 2,
+                    n_result: 1,
+                },
+            )]
+            .into_iter()
+            .collect(),
+            ..Default::default()
+        };
+        let mut interp = Interpreter::with_registry(registry);
+        interp.load_module(module).unwrap();
+        interp.push_call(start_dispatch(), 0).unwrap();
+        let mut collector = TestCollector::default();
         while interp.has_step() {
-            interp.step(&mut collector);
+            interp.step(&mut collector).unwrap();
         }
     }
 
     #[test]
     fn always_ready() {
-        let mut interp = Interpreter::new();
-        let poll_symbol = String::from("(poll)");
-        interp.load_module(Module {
+        let mut registry = NativeRegistry::standard();
+        let poll_symbol = Name::new("(poll)");
+        let module = Module {
             id: main_module(),
-            symbol_table: [(start_symbol(), 0), (poll_symbol.clone(), 12)]
+            symbol_table: [(start_symbol(), 0), (poll_symbol, 12)]
                 .into_iter()
                 .collect(),
             program: vec![
                 // closure
-                push_literal(Closure {
-                    dispatch: Dispatch {
-                        module_id: main_module(),
-                        symbol: poll_symbol.clone(),
+                push_literal(
+                    &mut registry,
+                    Closure {
+                        dispatch: Dispatch {
+                            module_id: main_module(),
+                            symbol: poll_symbol,
+                        },
+                        capture_list: Vec::new(),
                     },
-                    capture_list: Vec::new(),
-                }),
+                ),
                 // capture pack, closure
                 ByteCode::PackFloating(1),
                 // capture pack, closure*
-                ByteCode::Operate(2, Box::new(Closure::operate_capture)),
+                ByteCode::Operate(2, "closure.capture".into()),
                 // closure, capture pack
                 ByteCode::Copy(2),
                 // capture pack, dispatch, closure
-                ByteCode::Operate(1, Box::new(Closure::operate_apply)),
+                ByteCode::Operate(1, "closure.apply".into()),
                 // dispatch, capture pack | dispatch, closure
                 ByteCode::Copy(2),
                 // result, capture pack | dispatch, closure
@@ -200,24 +185,36 @@ mod tests {
                 // capture pack, closure, result
                 ByteCode::Copy(3),
                 // capture pack, closure*, result
-                ByteCode::Operate(2, Box::new(Closure::operate_capture)),
+                ByteCode::Operate(2, "closure.capture".into()),
                 // result
                 ByteCode::Copy(3),
                 ByteCode::Return(1),
                 // (poll)
                 ByteCode::Unpack,
                 ByteCode::AssertFloating(0),
-                push_literal(List(Vec::new())),
-                ByteCode::Operate(1, Box::new(Ready::operate_new)),
+                push_literal(&mut registry, List(Vec::new())),
+                ByteCode::Operate(1, "ready.new".into()),
                 ByteCode::PackFloating(2),
                 ByteCode::Copy(2),
                 ByteCode::Return(2),
             ],
-        });
-        interp.push_call(start_dispatch(), 0);
-        let mut collector = Collector::default();
+            signature_table: [(
+                poll_symbol,
+                Signature {
+                    n_parameter: 1,
+                    n_result: 2,
+                },
+            )]
+            .into_iter()
+            .collect(),
+            ..Default::default()
+        };
+        let mut interp = Interpreter::with_registry(registry);
+        interp.load_module(module).unwrap();
+        interp.push_call(start_dispatch(), 0).unwrap();
+        let mut collector = TestCollector::default();
         while interp.has_step() {
-            interp.step(&mut collector);
+            interp.step(&mut collector).unwrap();
         }
         let result_list = interp.reset();
         assert_eq!(result_list.len(), 1);
@@ -256,16 +253,16 @@ mod tests {
     //         program: vec![
     //             // async closure will be pushed externally
     //             ByteCode::AssertFloating(1),
-    //             ByteCode::Operate(1, Box::new(Closure::operate_apply)),
+    //             ByteCode::Operate(1, "closure.apply".into()),
     //             ByteCode::Call(1),
     //             ByteCode::PackFloating(1),
-    //             ByteCode::Operate(3, Box::new(Closure::operate_poll)),
+    //             ByteCode::Operate(3, "closure.poll".into()),
     //             ByteCode::Copy(3),
     //             ByteCode::Return(2),
     //             // (poll)
     //             ByteCode::Unpack,
     //             ByteCode::AssertFloating(1),
-    //             ByteCode::Operate(1, Box::new(Notify::operate_poll)),
+    //             ByteCode::Operate(1, "notify.poll".into()),
     //             // we don't actually need to capture Notify again on Ready, but anyway
     //             ByteCode::Copy(2),
     //             ByteCode::Return(2),
@@ -284,7 +281,7 @@ mod tests {
 
     //     let run_closure = |interp: &mut Interpreter| {
     //         interp.push_variable(notify_closure);
-    //         interp.push_call(start_dispatch(), 0);
+    //         interp.push_call(start_dispatch(), 0).unwrap();
     //         while interp.has_step() {
     //             interp.step();
     //         }
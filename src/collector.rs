@@ -2,10 +2,25 @@ use crate::{GeneralInterface, TaskId};
 use std::collections::{HashMap, HashSet};
 use std::mem::{replace, take};
 use std::ops::{Deref, DerefMut};
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::{Arc, Mutex, RwLock};
 
 pub type Address = (TaskId, u32);
 
+// not assigned to any task by portal
+pub const CONSTANT_HEAP_ID: TaskId = TaskId::MAX;
+
+// distinct for every collector created in the process, keying the constants an
+// interpreter has allocated in each of them
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CollectorId(u64);
+impl Default for CollectorId {
+    fn default() -> Self {
+        static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+        Self(NEXT_ID.fetch_add(1, Ordering::Relaxed))
+    }
+}
+
 pub trait EnumerateReference {
     fn enumerate_reference(&self, callback: &mut dyn FnMut(Address));
 }
@@ -17,19 +32,17 @@ impl Deref for Shared {
         &*self.0
     }
 }
-#[cfg(test)]
-impl From<Arc<dyn GeneralInterface>> for Shared {
-    fn from(value: Arc<dyn GeneralInterface>) -> Self {
-        Self(value)
-    }
-}
 
 #[derive(Default)]
 pub struct Collector {
+    id: CollectorId,
     heap_table: RwLock<HashMap<TaskId, Mutex<Heap>>>,
     limbo_table: RwLock<HashMap<Address, Arc<dyn GeneralInterface>>>,
     witness_set: Mutex<HashSet<TaskId>>,
     transfer_table: RwLock<HashMap<Address, Arc<dyn GeneralInterface>>>,
+    // module constants, kept for the lifetime of collector even after the module version
+    // they are loaded with is unloaded, so replacing modules repeatedly grows it
+    constant_heap: RwLock<Heap>,
 }
 
 #[derive(Default)]
@@ -43,14 +56,78 @@ impl Collector {
         Self::default()
     }
 
+    pub fn id(&self) -> CollectorId {
+        self.id
+    }
+
+    // keeps objects allocated for the task before it is spawned, e.g. its closure
     pub fn spawn(&self, id: TaskId) {
-        self.heap_table
-            .write()
-            .unwrap()
-            .insert(id, Default::default());
+        self.heap_table.write().unwrap().entry(id).or_default();
     }
 }
 
+// single heap without collection, for tests stepping an interpreter directly
+#[cfg(test)]
+#[derive(Default)]
+pub struct TestCollector {
+    id: CollectorId,
+    pub allocate_number: u32,
+    storage: HashMap<Address, Arc<dyn GeneralInterface>>,
+}
+#[cfg(test)]
+impl crate::runner::CollectorInterface for TestCollector {
+    fn allocate(&mut self, owned: Owned) -> Address {
+        self.allocate_number += 1;
+        let address = (0, self.allocate_number);
+        self.storage.insert(address, owned.0);
+        address
+    }
+    fn inspect(&self, address: Address) -> Shared {
+        Shared(self.storage.get(&address).unwrap().clone())
+    }
+    // in the same storage, but told apart by the heap id as with `Collector`
+    fn allocate_constant(&mut self, owned: Owned) -> Address {
+        self.allocate_number += 1;
+        let address = (CONSTANT_HEAP_ID, self.allocate_number);
+        self.storage.insert(address, owned.0);
+        address
+    }
+    fn collector_id(&self) -> CollectorId {
+        self.id
+    }
+    // same check as `Collector::replace_owned`
+    fn replace(&mut self, address: Address, owned: Owned) -> Owned {
+        let replaced = self.storage.insert(address, owned.0).unwrap();
+        assert_eq!(Arc::strong_count(&replaced), 1);
+        Owned(replaced)
+    }
+}
+
+// interpreter about to run `main.start` assembled from `source`, for tests that
+// watch it stepping into `main.callee`, whose dispatch native `push_callee` pushes
+#[cfg(test)]
+pub fn callee_interpreter(source: &str) -> crate::interpreter::Interpreter {
+    use crate::interpreter::Interpreter;
+    use crate::name::Name;
+    use crate::native::NativeRegistry;
+    use crate::objects::Dispatch;
+
+    let dispatch = |symbol| Dispatch {
+        module_id: Name::new("main"),
+        symbol: Name::new(symbol),
+    };
+    let mut registry = NativeRegistry::new();
+    registry.register("push_callee", 1, move |context| {
+        let dispatch = context.allocate(dispatch("callee").into());
+        context.push_result(dispatch);
+    });
+    let mut interp = Interpreter::with_registry(registry);
+    let module = crate::assembler::assemble(source).unwrap();
+    interp.load_module(module).unwrap();
+    interp.push_call(dispatch("start"), 0).unwrap();
+    interp
+}
+
 pub struct Owned(Arc<dyn GeneralInterface>);
 impl From<Box<dyn GeneralInterface>> for Owned {
     fn from(value: Box<dyn GeneralInterface>) -> Self {
@@ -62,18 +139,6 @@ impl<T: GeneralInterface> From<T> for Owned {
         Self(Arc::new(value))
     }
 }
-#[cfg(test)]
-impl Into<Arc<dyn GeneralInterface>> for Owned {
-    fn into(self) -> Arc<dyn GeneralInterface> {
-        self.0
-    }
-}
-#[cfg(test)]
-impl From<Arc<dyn GeneralInterface>> for Owned {
-    fn from(value: Arc<dyn GeneralInterface>) -> Self {
-        Self(value)
-    }
-}
 impl Deref for Owned {
     type Target = dyn GeneralInterface;
     fn deref(&self) -> &Self::Target {
@@ -96,10 +161,18 @@ impl Collector {
         address
     }
 
+    pub fn allocate_constant(&self, owned: Owned) -> Address {
+        let mut heap = self.constant_heap.write().unwrap();
+        heap.allocate_number += 1;
+        let address = (CONSTANT_HEAP_ID, heap.allocate_number);
+        heap.storage.insert(address, owned.0);
+        address
+    }
+
     pub fn inspect(&self, id: TaskId, address: Address) -> Shared {
         let heap_table = self.heap_table.read().unwrap();
         let mut heap = heap_table.get(&id).unwrap().lock().unwrap();
-        Shared(self.inspect_internal(address, &*heap_table, &mut *heap))
+        Shared(self.inspect_internal(address, &heap_table, &mut heap))
     }
 
     fn inspect_internal(
@@ -108,6 +181,10 @@ impl Collector {
         heap_table: &HashMap<TaskId, Mutex<Heap>>,
         heap: &mut Heap,
     ) -> Arc<dyn GeneralInterface> {
+        if address.0 == CONSTANT_HEAP_ID {
+            let constant_heap = self.constant_heap.read().unwrap();
+            return constant_heap.storage.get(&address).unwrap().clone();
+        }
         if let Some(shared) = heap.storage.get(&address) {
             shared.clone()
         } else {
@@ -130,6 +207,7 @@ impl Collector {
     }
 
     pub fn replace_owned(&self, address: Address, owned: Owned) -> Owned {
+        assert_ne!(address.0, CONSTANT_HEAP_ID, "constant is immutable");
         let heap_table = self.heap_table.read().unwrap();
         let mut heap = heap_table.get(&address.0).unwrap().lock().unwrap();
         let replaced = heap.storage.insert(address, owned.0).unwrap();
@@ -144,7 +222,11 @@ impl Collector {
         let heap_table = self.heap_table.read().unwrap();
         let mut heap = heap_table.get(&id).unwrap().lock().unwrap();
         while let Some(address) = gray_list.pop() {
-            let shared = self.inspect_internal(address, &*heap_table, &mut *heap);
+            // constants only refer to constants
+            if address.0 == CONSTANT_HEAP_ID {
+                continue;
+            }
+            let shared = self.inspect_internal(address, &heap_table, &mut heap);
             storage.insert(address, shared.clone());
             shared.enumerate_reference(&mut |address| {
                 if !storage.contains_key(&address) {
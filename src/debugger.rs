@@ -0,0 +1,288 @@
+// breakpoints and stepping on top of `Interpreter`
+//
+// a breakpoint stops execution before the instruction at its location is
+// stepped. every stepping operation steps at least one instruction, so
+// continuing from a breakpoint does not stop at it again immediately
+use crate::interpreter::{Interpreter, InterpreterError, ModuleId};
+use crate::name::Name;
+use crate::runner::CollectorInterface;
+use std::collections::BTreeSet;
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Location {
+    Symbol(Name),
+    Offset(usize),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Stop {
+    Breakpoint((ModuleId, usize)),
+    Stepped,
+    Finished,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BreakpointError {
+    UnknownModule(ModuleId),
+    UnknownSymbol(ModuleId, Name),
+    OutOfProgram(ModuleId, usize),
+}
+
+impl Display for BreakpointError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            BreakpointError::UnknownModule(module_id) => write!(f, "unknown module {module_id}"),
+            BreakpointError::UnknownSymbol(module_id, symbol) => {
+                write!(f, "unknown symbol {symbol} in module {module_id}")
+            }
+            BreakpointError::OutOfProgram(module_id, offset) => {
+                write!(f, "offset {offset} out of module {module_id}")
+            }
+        }
+    }
+}
+
+impl Error for BreakpointError {}
+
+#[derive(Debug, Default)]
+pub struct Debugger {
+    breakpoint_set: BTreeSet<(ModuleId, usize)>,
+}
+
+impl Debugger {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // symbols are resolved against modules already loaded into `interp`
+    pub fn add_breakpoint(
+        &mut self,
+        interp: &Interpreter,
+        module_id: ModuleId,
+        location: Location,
+    ) -> Result<(ModuleId, usize), BreakpointError> {
+        let pointer = Self::resolve(interp, module_id, location)?;
+        self.breakpoint_set.insert(pointer);
+        Ok(pointer)
+    }
+
+    pub fn remove_breakpoint(
+        &mut self,
+        interp: &Interpreter,
+        module_id: ModuleId,
+        location: Location,
+    ) -> Result<bool, BreakpointError> {
+        let pointer = Self::resolve(interp, module_id, location)?;
+        Ok(self.breakpoint_set.remove(&pointer))
+    }
+
+    pub fn breakpoints(&self) -> impl Iterator<Item = &(ModuleId, usize)> {
+        self.breakpoint_set.iter()
+    }
+
+    fn resolve(
+        interp: &Interpreter,
+        module_id: ModuleId,
+        location: Location,
+    ) -> Result<(ModuleId, usize), BreakpointError> {
+        let Some(module) = interp.module(module_id) else {
+            return Err(BreakpointError::UnknownModule(module_id));
+        };
+        let offset = match location {
+            Location::Symbol(symbol) => match module.symbol_table.get(&symbol) {
+                Some(offset) => *offset,
+                None => return Err(BreakpointError::UnknownSymbol(module_id, symbol)),
+            },
+            Location::Offset(offset) => offset,
+        };
+        if offset >= module.program.len() {
+            return Err(BreakpointError::OutOfProgram(module_id, offset));
+        }
+        Ok((module_id, offset))
+    }
+
+    pub fn step_into(
+        &self,
+        interp: &mut Interpreter,
+        collector: &mut dyn CollectorInterface,
+    ) -> Result<Stop, InterpreterError> {
+        self.run_while(interp, collector, |_| false)
+    }
+
+    // step through any call made by the current instruction
+    pub fn step_over(
+        &self,
+        interp: &mut Interpreter,
+        collector: &mut dyn CollectorInterface,
+    ) -> Result<Stop, InterpreterError> {
+        let depth = interp.call_stack().len();
+        self.run_while(interp, collector, |interp| {
+            interp.call_stack().len() > depth
+        })
+    }
+
+    // run until the current frame returns
+    pub fn step_out(
+        &self,
+        interp: &mut Interpreter,
+        collector: &mut dyn CollectorInterface,
+    ) -> Result<Stop, InterpreterError> {
+        let depth = interp.call_stack().len();
+        self.run_while(interp, collector, |interp| {
+            interp.call_stack().len() >= depth
+        })
+    }
+
+    pub fn resume(
+        &self,
+        interp: &mut Interpreter,
+        collector: &mut dyn CollectorInterface,
+    ) -> Result<Stop, InterpreterError> {
+        self.run_while(interp, collector, |_| true)
+    }
+
+    fn run_while(
+        &self,
+        interp: &mut Interpreter,
+        collector: &mut dyn CollectorInterface,
+        mut keep_running: impl FnMut(&Interpreter) -> bool,
+    ) -> Result<Stop, InterpreterError> {
+        while interp.has_step() {
+            interp.step(collector)?;
+            let Some(frame) = interp.call_stack().last() else {
+                break;
+            };
+            if self.breakpoint_set.contains(&frame.pointer) {
+                return Ok(Stop::Breakpoint(frame.pointer));
+            }
+            if !keep_running(interp) {
+                return Ok(Stop::Stepped);
+            }
+        }
+        Ok(Stop::Finished)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::collector::{callee_interpreter, TestCollector};
+    use crate::objects::Dispatch;
+
+    fn main_pointer(offset: usize) -> (ModuleId, usize) {
+        (Name::new("main"), offset)
+    }
+
+    fn interp() -> Interpreter {
+        callee_interpreter(
+            "
+                .module main
+                .symbol start
+                    operate 0 push_callee
+                    call 0
+                    return 0
+                .symbol callee
+                    operate 0 push_callee
+                    return 0
+            ",
+        )
+    }
+
+    #[test]
+    fn step() {
+        let debugger = Debugger::new();
+        let mut interp = interp();
+        let mut collector = TestCollector::default();
+        assert_eq!(
+            debugger.step_into(&mut interp, &mut collector),
+            Ok(Stop::Stepped)
+        );
+        assert_eq!(interp.call_stack()[0].pointer, main_pointer(1));
+        assert_eq!(
+            debugger.step_over(&mut interp, &mut collector),
+            Ok(Stop::Stepped)
+        );
+        assert_eq!(interp.call_stack().len(), 1);
+        assert_eq!(interp.call_stack()[0].pointer, main_pointer(2));
+        assert_eq!(
+            debugger.step_into(&mut interp, &mut collector),
+            Ok(Stop::Finished)
+        );
+        assert_eq!(
+            debugger.step_into(&mut interp, &mut collector),
+            Ok(Stop::Finished)
+        );
+    }
+
+    #[test]
+    fn breakpoint() {
+        let mut debugger = Debugger::new();
+        let mut interp = interp();
+        let mut collector = TestCollector::default();
+        let callee = Location::Symbol(Name::new("callee"));
+        assert_eq!(
+            debugger.add_breakpoint(&interp, Name::new("main"), callee.clone()),
+            Ok(main_pointer(3))
+        );
+
+        assert_eq!(
+            debugger.resume(&mut interp, &mut collector),
+            Ok(Stop::Breakpoint(main_pointer(3)))
+        );
+        let frame_list = interp.call_stack();
+        assert_eq!(frame_list.len(), 2);
+        assert_eq!(frame_list[0].pointer, main_pointer(2));
+        assert_eq!(frame_list[0].stack_size, 0);
+        assert!(interp.stack_view(&collector).is_empty());
+
+        assert_eq!(
+            debugger.step_into(&mut interp, &mut collector),
+            Ok(Stop::Stepped)
+        );
+        let stack = interp.stack_view(&collector);
+        assert_eq!(stack.len(), 1);
+        assert!(stack[0].as_ref().is::<Dispatch>());
+
+        assert_eq!(
+            debugger.step_out(&mut interp, &mut collector),
+            Ok(Stop::Stepped)
+        );
+        assert_eq!(interp.call_stack().len(), 1);
+        assert_eq!(
+            debugger.remove_breakpoint(&interp, Name::new("main"), callee),
+            Ok(true)
+        );
+        assert_eq!(
+            debugger.resume(&mut interp, &mut collector),
+            Ok(Stop::Finished)
+        );
+    }
+
+    #[test]
+    fn reject_invalid_breakpoint() {
+        let mut debugger = Debugger::new();
+        let interp = interp();
+        assert_eq!(
+            debugger.add_breakpoint(&interp, Name::new("other"), Location::Offset(0)),
+            Err(BreakpointError::UnknownModule(Name::new("other")))
+        );
+        assert_eq!(
+            debugger.add_breakpoint(
+                &interp,
+                Name::new("main"),
+                Location::Symbol(Name::new("end"))
+            ),
+            Err(BreakpointError::UnknownSymbol(
+                Name::new("main"),
+                Name::new("end")
+            ))
+        );
+        assert_eq!(
+            debugger.add_breakpoint(&interp, Name::new("main"), Location::Offset(5)),
+            Err(BreakpointError::OutOfProgram(Name::new("main"), 5))
+        );
+        assert_eq!(debugger.breakpoints().count(), 0);
+    }
+}
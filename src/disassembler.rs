@@ -0,0 +1,283 @@
+// text for loaded modules in the assembler syntax, assembled back into an equal
+// module. jump targets get a symbol at the target or a generated `@<offset>`
+// label, and only jumps out of the program keep their raw relative offset. every
+// instruction is commented with its offset, and jumps with their absolute target
+use crate::interpreter::{jump_target, ByteCode, Constant, Interpreter, Module};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::{self, Display, Formatter, Write};
+
+impl Display for ByteCode {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ByteCode::Copy(offset) => write!(f, "copy {offset}"),
+            ByteCode::Operate(n_argument, native_id) => {
+                write!(f, "operate {n_argument} {native_id}")
+            }
+            ByteCode::Jump(offset) => write!(f, "jump {offset:+}"),
+            ByteCode::Goto(offset) => write!(f, "goto {offset:+}"),
+            ByteCode::JumpIf(offset) => write!(f, "jump_if {offset:+}"),
+            ByteCode::JumpUnless(offset) => write!(f, "jump_unless {offset:+}"),
+            ByteCode::Call(n_argument) => write!(f, "call {n_argument}"),
+            ByteCode::TailCall(n_argument) => write!(f, "tail_call {n_argument}"),
+            ByteCode::Return(n_returned) => write!(f, "return {n_returned}"),
+            ByteCode::AssertFloating(n_floating) => write!(f, "assert_floating {n_floating}"),
+            ByteCode::PackFloating(n_destructed) => write!(f, "pack_floating {n_destructed}"),
+            ByteCode::Unpack => write!(f, "unpack"),
+            ByteCode::Try(offset) => write!(f, "try {offset:+}"),
+            ByteCode::EndTry => write!(f, "end_try"),
+            ByteCode::Throw => write!(f, "throw"),
+            ByteCode::Yield(n_yielded) => write!(f, "yield {n_yielded}"),
+            ByteCode::Resume(n_argument) => write!(f, "resume {n_argument}"),
+            ByteCode::LoadConstant(index) => write!(f, "load_constant {index}"),
+            ByteCode::LoadLocal(index) => write!(f, "load_local {index}"),
+            ByteCode::StoreLocal(index) => write!(f, "store_local {index}"),
+            ByteCode::Pop => write!(f, "pop"),
+            ByteCode::Swap => write!(f, "swap"),
+            ByteCode::Rotate(n) => write!(f, "rotate {n}"),
+        }
+    }
+}
+
+impl ByteCode {
+    pub fn mnemonic(&self) -> &'static str {
+        match self {
+            ByteCode::Copy(_) => "copy",
+            ByteCode::Operate(..) => "operate",
+            ByteCode::Jump(_) => "jump",
+            ByteCode::Goto(_) => "goto",
+            ByteCode::JumpIf(_) => "jump_if",
+            ByteCode::JumpUnless(_) => "jump_unless",
+            ByteCode::Call(_) => "call",
+            ByteCode::TailCall(_) => "tail_call",
+            ByteCode::Return(_) => "return",
+            ByteCode::AssertFloating(_) => "assert_floating",
+            ByteCode::PackFloating(_) => "pack_floating",
+            ByteCode::Unpack => "unpack",
+            ByteCode::Try(_) => "try",
+            ByteCode::EndTry => "end_try",
+            ByteCode::Throw => "throw",
+            ByteCode::Yield(_) => "yield",
+            ByteCode::Resume(_) => "resume",
+            ByteCode::LoadConstant(_) => "load_constant",
+            ByteCode::LoadLocal(_) => "load_local",
+            ByteCode::StoreLocal(_) => "store_local",
+            ByteCode::Pop => "pop",
+            ByteCode::Swap => "swap",
+            ByteCode::Rotate(_) => "rotate",
+        }
+    }
+}
+
+impl Display for Constant {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Constant::True => write!(f, "true"),
+            Constant::False => write!(f, "false"),
+            Constant::Dispatch(dispatch) => {
+                write!(f, "dispatch {} {}", dispatch.module_id, dispatch.symbol)
+            }
+            Constant::Integer(value) => write!(f, "integer {value}"),
+            // `{:?}` keeps the fraction of integral values, e.g. `1.0`
+            Constant::Float(value) => write!(f, "float {value:?}"),
+        }
+    }
+}
+
+pub fn disassemble(module: &Module) -> String {
+    let mut symbol_list: Vec<_> = module.symbol_table.iter().collect();
+    symbol_list.sort_by_key(|(symbol, offset)| (**offset, *symbol));
+    let mut symbol_list = symbol_list.into_iter().peekable();
+
+    let mut text = String::new();
+    writeln!(text, ".module {}", module.id).unwrap();
+    for (module_id, symbol) in &module.import_list {
+        writeln!(text, ".import {module_id} {symbol}").unwrap();
+    }
+    let mut export_list: Vec<_> = module.export_set.iter().collect();
+    export_list.sort();
+    for symbol in export_list {
+        writeln!(text, ".export {symbol}").unwrap();
+    }
+    for (index, constant) in module.constant_list.iter().enumerate() {
+        let line = format!(".constant {constant}");
+        writeln!(text, "{line:<40}; {index}").unwrap();
+    }
+    let symbol_line = |symbol| match module.signature_table.get(symbol) {
+        Some(signature) => format!(
+            ".symbol {symbol} {} {}",
+            signature.n_parameter, signature.n_result
+        ),
+        None => format!(".symbol {symbol}"),
+    };
+    // a symbol is also a label, so only other targets get a generated one
+    let mut symbol_at = BTreeMap::new();
+    for (symbol, offset) in symbol_list.clone() {
+        symbol_at.entry(*offset).or_insert(symbol);
+    }
+    let target_label = |offset, instruction| {
+        let target = usize::try_from(jump_target(offset, instruction)?).ok()?;
+        if target > module.program.len() {
+            return None;
+        }
+        Some(match symbol_at.get(&target) {
+            Some(symbol) => symbol.to_string(),
+            None => format!("@{target}"),
+        })
+    };
+    let label_set: BTreeSet<_> = module
+        .program
+        .iter()
+        .enumerate()
+        .filter_map(|(offset, instruction)| target_label(offset, instruction))
+        .filter(|label| label.starts_with('@'))
+        .collect();
+    let label_line = |offset: usize| {
+        let label = format!("@{offset}");
+        label_set.contains(&label).then(|| format!("{label}:"))
+    };
+
+    for (offset, instruction) in module.program.iter().enumerate() {
+        while let Some((symbol, _)) = symbol_list.next_if(|(_, entry)| **entry == offset) {
+            writeln!(text, "{}", symbol_line(symbol)).unwrap();
+        }
+        if let Some(line) = label_line(offset) {
+            writeln!(text, "{line}").unwrap();
+        }
+        let line = match target_label(offset, instruction) {
+            Some(label) => format!("    {} {label}", instruction.mnemonic()),
+            None => format!("    {instruction}"),
+        };
+        match jump_target(offset, instruction) {
+            Some(target) => writeln!(text, "{line:<40}; {offset} -> {target}").unwrap(),
+            None => writeln!(text, "{line:<40}; {offset}").unwrap(),
+        }
+    }
+    if let Some(line) = label_line(module.program.len()) {
+        writeln!(text, "{line}").unwrap();
+    }
+    // symbols pointing at or past the end of program, the ones past it are
+    // moved to the end when assembled back
+    for (symbol, offset) in symbol_list {
+        writeln!(text, "{}  ; at {offset}", symbol_line(symbol)).unwrap();
+    }
+    text
+}
+
+pub fn disassemble_interpreter(interp: &Interpreter) -> String {
+    let mut module_list: Vec<_> = interp.modules().collect();
+    module_list.sort_by_key(|module| module.id);
+    module_list
+        .into_iter()
+        .map(disassemble)
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assembler::assemble;
+    use crate::interpreter::Signature;
+    use crate::name::Name;
+
+    fn module(id: &str) -> Module {
+        Module {
+            id: Name::new(id),
+            symbol_table: [(Name::new("start"), 0), (Name::new("loop"), 1)]
+                .into_iter()
+                .collect(),
+            program: vec![
+                ByteCode::AssertFloating(1),
+                ByteCode::Operate(1, "closure.apply".into()),
+                ByteCode::Jump(-2),
+                ByteCode::LoadConstant(0),
+                ByteCode::Return(1),
+            ],
+            signature_table: [("start", 1), ("loop", 2)]
+                .into_iter()
+                .map(|(symbol, n_parameter)| {
+                    let signature = Signature {
+                        n_parameter,
+                        n_result: 1,
+                    };
+                    (Name::new(symbol), signature)
+                })
+                .collect(),
+            constant_list: vec![Constant::True],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn annotated_module() {
+        assert_eq!(
+            disassemble(&module("main")),
+            "\
+.module main
+.constant true                          ; 0
+.symbol start 1 1
+    assert_floating 1                   ; 0
+.symbol loop 2 1
+    operate 1 closure.apply             ; 1
+    jump loop                           ; 2 -> 1
+    load_constant 0                     ; 3
+    return 1                            ; 4
+"
+        );
+    }
+
+    #[test]
+    fn every_loaded_module() {
+        let mut interp = Interpreter::new();
+        interp.load_module(module("b")).unwrap();
+        interp.load_module(module("a")).unwrap();
+        let text = disassemble_interpreter(&interp);
+        let a = text.find(".module a").unwrap();
+        let b = text.find(".module b").unwrap();
+        assert!(a < b);
+        assert_eq!(text.matches(".symbol loop").count(), 2);
+    }
+
+    #[test]
+    fn round_trip() {
+        let module = assemble(
+            "
+                .module main
+                .import lib double
+                .export start
+                .export fib
+                .constant dispatch lib double
+                .constant integer -42
+                .constant float 0.5
+                .constant false
+                .symbol start
+                    try handler
+                    load_constant 0
+                    call 0
+                    end_try
+                    jump_unless done
+                    goto start
+                handler:
+                    pop
+                done:
+                    return 0
+                .symbol fib 1 1
+                    jump +2
+                    goto end
+                    jump_if -20
+                end:
+            ",
+        )
+        .unwrap();
+        let text = disassemble(&module);
+        assert!(text.contains("jump_unless @7                      ; 4 -> 7\n"));
+        assert!(text.contains("goto start                          ; 5 -> 0\n"));
+        assert!(text.contains("jump @11                            ; 8 -> 11\n"));
+        assert!(text.contains("jump_if -20                         ; 10 -> -9\n"));
+        assert_eq!(assemble(&text).unwrap(), module);
+        assert_eq!(
+            assemble(&disassemble(&self::module("main"))),
+            Ok(self::module("main"))
+        );
+    }
+}
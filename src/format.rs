@@ -0,0 +1,483 @@
+// compiled module file (.gmkc), all integers in little endian
+//
+//     magic "GMKC", u16 version. only `FORMAT_VERSION` is read, as the layout of any
+//         other version differs from the one below
+//     constant section: u32 count, (u32 length, utf-8 bytes) for each string
+//     u32 module id (constant index)
+//     symbol table: u32 count, (u32 symbol constant index, u32 offset) for each entry
+//     signatures: u32 count, (u32 symbol constant index, u8 parameters, u8 results) for
+//         each, sorted
+//     imports: u32 count, (u32 module constant index, u32 symbol constant index) for each
+//     exports: u32 count, u32 symbol constant index for each, sorted
+//     constant pool: u32 count, (u8 tag, operands) for each `Constant`
+//     program: u32 count, (u8 opcode, operands) for each instruction
+//     u32 FNV-1a checksum of all preceding bytes
+use crate::interpreter::{ByteCode, Constant, Module, Signature};
+use crate::name::Name;
+use crate::objects::Dispatch;
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+use std::io::{self, Read, Write};
+
+pub const MAGIC: &[u8; 4] = b"GMKC";
+pub const FORMAT_VERSION: u16 = 4;
+pub const EXTENSION: &str = "gmkc";
+
+#[derive(Debug)]
+pub enum FormatError {
+    Io(io::Error),
+    BadMagic,
+    UnsupportedVersion(u16),
+    ChecksumMismatch,
+    Truncated,
+    TrailingData,
+    InvalidOpcode(u8),
+    InvalidConstant(u32),
+    InvalidUtf8,
+    InvalidConstantTag(u8),
+}
+
+impl Display for FormatError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            FormatError::Io(error) => write!(f, "{error}"),
+            FormatError::BadMagic => write!(f, "not a compiled module"),
+            FormatError::UnsupportedVersion(version) => {
+                write!(f, "unsupported format version {version}")
+            }
+            FormatError::ChecksumMismatch => write!(f, "checksum mismatch"),
+            FormatError::Truncated => write!(f, "unexpected end of module"),
+            FormatError::TrailingData => write!(f, "unexpected data after module"),
+            FormatError::InvalidOpcode(opcode) => write!(f, "invalid opcode {opcode}"),
+            FormatError::InvalidConstant(index) => write!(f, "invalid constant index {index}"),
+            FormatError::InvalidUtf8 => write!(f, "invalid utf-8 in constant"),
+            FormatError::InvalidConstantTag(tag) => write!(f, "invalid constant tag {tag}"),
+        }
+    }
+}
+
+impl Error for FormatError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            FormatError::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for FormatError {
+    fn from(error: io::Error) -> Self {
+        Self::Io(error)
+    }
+}
+
+fn checksum(bytes: &[u8]) -> u32 {
+    bytes.iter().fold(0x811c9dc5, |hash, byte| {
+        (hash ^ *byte as u32).wrapping_mul(0x01000193)
+    })
+}
+
+#[derive(Default)]
+struct ConstantSection<'m> {
+    constant_list: Vec<&'m str>,
+    index_table: HashMap<&'m str, u32>,
+}
+
+impl<'m> ConstantSection<'m> {
+    fn intern(&mut self, constant: &'m str) -> u32 {
+        *self.index_table.entry(constant).or_insert_with(|| {
+            self.constant_list.push(constant);
+            self.constant_list.len() as u32 - 1
+        })
+    }
+}
+
+fn put_u32(bytes: &mut Vec<u8>, value: u32) {
+    bytes.extend(value.to_le_bytes());
+}
+
+impl Module {
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        let mut constant_section = ConstantSection::default();
+        let mut body = Vec::new();
+        put_u32(&mut body, constant_section.intern(&self.id));
+
+        let mut symbol_list: Vec<_> = self.symbol_table.iter().collect();
+        symbol_list.sort();
+        put_u32(&mut body, symbol_list.len() as u32);
+        for (symbol, offset) in symbol_list {
+            put_u32(&mut body, constant_section.intern(symbol));
+            put_u32(&mut body, *offset as u32);
+        }
+        let mut signature_list: Vec<_> = self.signature_table.iter().collect();
+        signature_list.sort_by_key(|(symbol, _)| **symbol);
+        put_u32(&mut body, signature_list.len() as u32);
+        for (symbol, signature) in signature_list {
+            put_u32(&mut body, constant_section.intern(symbol));
+            body.extend([signature.n_parameter, signature.n_result]);
+        }
+
+        put_u32(&mut body, self.import_list.len() as u32);
+        for (module_id, symbol) in &self.import_list {
+            put_u32(&mut body, constant_section.intern(module_id));
+            put_u32(&mut body, constant_section.intern(symbol));
+        }
+        let mut export_list: Vec<_> = self.export_set.iter().collect();
+        export_list.sort();
+        put_u32(&mut body, export_list.len() as u32);
+        for symbol in export_list {
+            put_u32(&mut body, constant_section.intern(symbol));
+        }
+
+        put_u32(&mut body, self.constant_list.len() as u32);
+        for constant in &self.constant_list {
+            match constant {
+                Constant::True => body.push(0),
+                Constant::False => body.push(1),
+                Constant::Dispatch(dispatch) => {
+                    body.push(2);
+                    put_u32(&mut body, constant_section.intern(&dispatch.module_id));
+                    put_u32(&mut body, constant_section.intern(&dispatch.symbol));
+                }
+                Constant::Integer(value) => {
+                    body.push(3);
+                    body.extend(value.to_le_bytes());
+                }
+                Constant::Float(value) => {
+                    body.push(4);
+                    body.extend(value.to_bits().to_le_bytes());
+                }
+            }
+        }
+
+        put_u32(&mut body, self.program.len() as u32);
+        for instruction in &self.program {
+            match instruction {
+                ByteCode::Copy(offset) => body.extend([0, *offset]),
+                ByteCode::Operate(n_argument, native_id) => {
+                    body.extend([1, *n_argument]);
+                    put_u32(&mut body, constant_section.intern(native_id));
+                }
+                ByteCode::Jump(offset) => body.extend([2, *offset as u8]),
+                ByteCode::Call(n_argument) => body.extend([3, *n_argument]),
+                ByteCode::Return(n_returned) => body.extend([4, *n_returned]),
+                ByteCode::AssertFloating(n_floating) => body.extend([5, *n_floating]),
+                ByteCode::PackFloating(n_destructed) => body.extend([6, *n_destructed]),
+                ByteCode::Unpack => body.push(7),
+                ByteCode::Goto(offset) => {
+                    body.push(8);
+                    body.extend(offset.to_le_bytes());
+                }
+                ByteCode::JumpIf(offset) => {
+                    body.push(9);
+                    body.extend(offset.to_le_bytes());
+                }
+                ByteCode::JumpUnless(offset) => {
+                    body.push(10);
+                    body.extend(offset.to_le_bytes());
+                }
+                ByteCode::TailCall(n_argument) => body.extend([11, *n_argument]),
+                ByteCode::Try(offset) => {
+                    body.push(12);
+                    body.extend(offset.to_le_bytes());
+                }
+                ByteCode::EndTry => body.push(13),
+                ByteCode::Throw => body.push(14),
+                ByteCode::Yield(n_yielded) => body.extend([15, *n_yielded]),
+                ByteCode::Resume(n_argument) => body.extend([16, *n_argument]),
+                ByteCode::LoadConstant(index) => {
+                    body.push(17);
+                    body.extend(index.to_le_bytes());
+                }
+                ByteCode::LoadLocal(index) => body.extend([18, *index]),
+                ByteCode::StoreLocal(index) => body.extend([19, *index]),
+                ByteCode::Pop => body.push(20),
+                ByteCode::Swap => body.push(21),
+                ByteCode::Rotate(n) => body.extend([22, *n]),
+            }
+        }
+
+        let mut bytes = Vec::from(*MAGIC);
+        bytes.extend(FORMAT_VERSION.to_le_bytes());
+        put_u32(&mut bytes, constant_section.constant_list.len() as u32);
+        for constant in &constant_section.constant_list {
+            put_u32(&mut bytes, constant.len() as u32);
+            bytes.extend(constant.as_bytes());
+        }
+        bytes.extend(body);
+        let checksum = checksum(&bytes);
+        put_u32(&mut bytes, checksum);
+        writer.write_all(&bytes)
+    }
+
+    pub fn read_from<R: Read>(reader: &mut R) -> Result<Self, FormatError> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes)?;
+        if bytes.len() < MAGIC.len() || &bytes[..MAGIC.len()] != MAGIC {
+            return Err(FormatError::BadMagic);
+        }
+        let mut cursor = Cursor(&bytes[MAGIC.len()..]);
+        let version = u16::from_le_bytes(cursor.take()?);
+        if version != FORMAT_VERSION {
+            return Err(FormatError::UnsupportedVersion(version));
+        }
+        let (content, expected) = bytes.split_at(bytes.len().saturating_sub(4));
+        if content.len() < MAGIC.len() + 2 {
+            return Err(FormatError::Truncated);
+        }
+        if checksum(content).to_le_bytes() != expected {
+            return Err(FormatError::ChecksumMismatch);
+        }
+        let mut cursor = Cursor(&content[MAGIC.len() + 2..]);
+
+        let mut constant_list = Vec::new();
+        for _ in 0..cursor.u32()? {
+            let length = cursor.u32()? as usize;
+            let constant = cursor.slice(length)?;
+            let constant = std::str::from_utf8(constant).map_err(|_| FormatError::InvalidUtf8)?;
+            constant_list.push(constant.to_string());
+        }
+        let constant = |cursor: &mut Cursor| {
+            let index = cursor.u32()?;
+            constant_list
+                .get(index as usize)
+                .cloned()
+                .ok_or(FormatError::InvalidConstant(index))
+        };
+
+        let name = |cursor: &mut Cursor| constant(cursor).map(Name::from);
+
+        let id = name(&mut cursor)?;
+        let mut symbol_table = HashMap::new();
+        for _ in 0..cursor.u32()? {
+            let symbol = name(&mut cursor)?;
+            symbol_table.insert(symbol, cursor.u32()? as usize);
+        }
+        let mut signature_table = HashMap::new();
+        for _ in 0..cursor.u32()? {
+            let symbol = name(&mut cursor)?;
+            let signature = Signature {
+                n_parameter: cursor.u8()?,
+                n_result: cursor.u8()?,
+            };
+            signature_table.insert(symbol, signature);
+        }
+        let mut import_list = Vec::new();
+        for _ in 0..cursor.u32()? {
+            let module_id = name(&mut cursor)?;
+            import_list.push((module_id, name(&mut cursor)?));
+        }
+        let mut export_set = HashSet::new();
+        for _ in 0..cursor.u32()? {
+            export_set.insert(name(&mut cursor)?);
+        }
+        let mut constant_pool = Vec::new();
+        for _ in 0..cursor.u32()? {
+            let [tag] = cursor.take()?;
+            constant_pool.push(match tag {
+                0 => Constant::True,
+                1 => Constant::False,
+                2 => Constant::Dispatch(Dispatch {
+                    module_id: name(&mut cursor)?,
+                    symbol: name(&mut cursor)?,
+                }),
+                3 => Constant::Integer(i64::from_le_bytes(cursor.take()?)),
+                4 => Constant::Float(f64::from_bits(u64::from_le_bytes(cursor.take()?))),
+                _ => return Err(FormatError::InvalidConstantTag(tag)),
+            });
+        }
+        let mut program = Vec::new();
+        for _ in 0..cursor.u32()? {
+            let [opcode] = cursor.take()?;
+            program.push(match opcode {
+                0 => ByteCode::Copy(cursor.u8()?),
+                1 => {
+                    let n_argument = cursor.u8()?;
+                    ByteCode::Operate(n_argument, constant(&mut cursor)?)
+                }
+                2 => ByteCode::Jump(cursor.u8()? as i8),
+                3 => ByteCode::Call(cursor.u8()?),
+                4 => ByteCode::Return(cursor.u8()?),
+                5 => ByteCode::AssertFloating(cursor.u8()?),
+                6 => ByteCode::PackFloating(cursor.u8()?),
+                7 => ByteCode::Unpack,
+                8 => ByteCode::Goto(cursor.i32()?),
+                9 => ByteCode::JumpIf(cursor.i32()?),
+                10 => ByteCode::JumpUnless(cursor.i32()?),
+                11 => ByteCode::TailCall(cursor.u8()?),
+                12 => ByteCode::Try(cursor.i32()?),
+                13 => ByteCode::EndTry,
+                14 => ByteCode::Throw,
+                15 => ByteCode::Yield(cursor.u8()?),
+                16 => ByteCode::Resume(cursor.u8()?),
+                17 => ByteCode::LoadConstant(cursor.u16()?),
+                18 => ByteCode::LoadLocal(cursor.u8()?),
+                19 => ByteCode::StoreLocal(cursor.u8()?),
+                20 => ByteCode::Pop,
+                21 => ByteCode::Swap,
+                22 => ByteCode::Rotate(cursor.u8()?),
+                _ => return Err(FormatError::InvalidOpcode(opcode)),
+            });
+        }
+        if !cursor.0.is_empty() {
+            return Err(FormatError::TrailingData);
+        }
+        Ok(Self {
+            id,
+            program,
+            symbol_table,
+            signature_table,
+            import_list,
+            export_set,
+            constant_list: constant_pool,
+        })
+    }
+}
+
+struct Cursor<'b>(&'b [u8]);
+
+impl<'b> Cursor<'b> {
+    fn slice(&mut self, length: usize) -> Result<&'b [u8], FormatError> {
+        if self.0.len() < length {
+            return Err(FormatError::Truncated);
+        }
+        let (slice, rest) = self.0.split_at(length);
+        self.0 = rest;
+        Ok(slice)
+    }
+
+    fn take<const N: usize>(&mut self) -> Result<[u8; N], FormatError> {
+        Ok(self.slice(N)?.try_into().unwrap())
+    }
+
+    fn u8(&mut self) -> Result<u8, FormatError> {
+        Ok(self.take::<1>()?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, FormatError> {
+        Ok(u16::from_le_bytes(self.take()?))
+    }
+
+    fn u32(&mut self) -> Result<u32, FormatError> {
+        Ok(u32::from_le_bytes(self.take()?))
+    }
+
+    fn i32(&mut self) -> Result<i32, FormatError> {
+        Ok(i32::from_le_bytes(self.take()?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assembler::assemble;
+    use crate::interpreter::{Interpreter, LoadError};
+
+    fn module() -> Module {
+        assemble(
+            "
+            .module main
+            .import lib double
+            .import main back
+            .export start
+            .constant true
+            .constant dispatch lib double
+            .constant integer -42
+            .constant float 0.5
+            .symbol start
+                assert_floating 2
+                load_constant 1
+                load_local 0
+                store_local 0
+                swap
+                rotate 3
+                pop
+                operate 1 closure.apply
+                copy 2
+                call 1
+                pack_floating 0
+                unpack
+                assert_floating 2
+            .symbol back 2 1
+                jump start
+                goto back
+                jump_if start
+                jump_unless -4
+                tail_call 1
+                try start
+                end_try
+                throw
+                resume 1
+                yield 2
+                return 1
+            ",
+        )
+        .unwrap()
+    }
+
+    fn compiled() -> Vec<u8> {
+        let mut bytes = Vec::new();
+        module().write_to(&mut bytes).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn round_trip() {
+        let bytes = compiled();
+        assert_eq!(&bytes[..4], MAGIC);
+        assert_eq!(Module::read_from(&mut &bytes[..]).unwrap(), module());
+        // stable output regardless of symbol table iteration order
+        assert_eq!(compiled(), bytes);
+    }
+
+    #[test]
+    fn reject_invalid_file() {
+        let read = |bytes: Vec<u8>| Module::read_from(&mut &bytes[..]).unwrap_err();
+        assert!(matches!(read(b"GMK".to_vec()), FormatError::BadMagic));
+
+        let mut bytes = compiled();
+        bytes[4] = 42;
+        assert!(matches!(read(bytes), FormatError::UnsupportedVersion(42)));
+
+        let mut bytes = compiled();
+        let middle = bytes.len() / 2;
+        bytes[middle] ^= 0xff;
+        assert!(matches!(read(bytes), FormatError::ChecksumMismatch));
+
+        let mut bytes = compiled();
+        bytes.truncate(bytes.len() - 7);
+        let checksum = checksum(&bytes);
+        put_u32(&mut bytes, checksum);
+        assert!(matches!(read(bytes), FormatError::Truncated));
+    }
+
+    #[test]
+    fn reject_previous_version() {
+        for version in 1..FORMAT_VERSION {
+            let mut bytes = compiled();
+            bytes.truncate(bytes.len() - 4);
+            bytes[4..6].copy_from_slice(&version.to_le_bytes());
+            let checksum = checksum(&bytes);
+            put_u32(&mut bytes, checksum);
+            assert!(matches!(
+                Module::read_from(&mut &bytes[..]),
+                Err(FormatError::UnsupportedVersion(v)) if v == version
+            ));
+        }
+    }
+
+    #[test]
+    fn load_compiled() {
+        let mut interp = Interpreter::new();
+        interp.load_from(&mut &compiled()[..]).unwrap();
+        assert_eq!(interp.modules().next(), Some(&module()));
+
+        let mut bytes = compiled();
+        bytes.pop();
+        assert!(matches!(
+            interp.load_from(&mut &bytes[..]),
+            Err(LoadError::Format(FormatError::ChecksumMismatch))
+        ));
+    }
+}
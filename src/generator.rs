@@ -0,0 +1,269 @@
+use crate::interpreter::OperateContext;
+use crate::objects::{Dispatch, Generator, GeneratorState, TypeMismatch};
+
+impl Generator {
+    // arguments: 1 Dispatch
+    // result: 1 Generator, running the dispatched function on first resume, or throws
+    // `TypeMismatch` and pushes nothing
+    pub fn operate_new(context: &mut dyn OperateContext) {
+        let dispatch = context.inspect(context.get_argument(0));
+        let Some(dispatch) = dispatch.as_ref().downcast_ref::<Dispatch>().copied() else {
+            let error = context.allocate(TypeMismatch.into());
+            context.throw(error);
+            return;
+        };
+        let generator = Generator {
+            dispatch,
+            state: GeneratorState::Created,
+        };
+        let generator = context.allocate(generator.into());
+        context.push_result(generator);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assembler::assemble;
+    use crate::collector::{Address, EnumerateReference, TestCollector};
+    use crate::interpreter::{ErrorKind, Frame, Interpreter, InterpreterError};
+    use crate::name::Name;
+    use crate::native::NativeRegistry;
+    use crate::objects::Integer;
+    use crate::runner::CollectorInterface;
+
+    use std::sync::{Arc, Mutex};
+
+    fn dispatch(symbol: &str) -> Dispatch {
+        Dispatch {
+            module_id: Name::new("main"),
+            symbol: Name::new(symbol),
+        }
+    }
+
+    fn registry() -> NativeRegistry {
+        let mut registry = NativeRegistry::standard();
+        for symbol in ["count", "consume", "start"] {
+            registry.register(format!("push_{symbol}"), 1, move |context| {
+                let dispatch = context.allocate(dispatch(symbol).into());
+                context.push_result(dispatch);
+            });
+        }
+        registry
+    }
+
+    fn run(registry: NativeRegistry, source: &str) -> Result<(), ErrorKind> {
+        let mut interp = Interpreter::with_registry(registry);
+        interp.load_module(assemble(source).unwrap()).unwrap();
+        interp.push_call(dispatch("start"), 0).unwrap();
+        let mut collector = TestCollector::default();
+        while interp.has_step() {
+            interp.step(&mut collector).map_err(|error| error.kind)?;
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn yield_sequence() {
+        let mut registry = registry();
+        let record = Arc::new(Mutex::new(Vec::new()));
+        let native_record = record.clone();
+        registry.register("record", 0, move |context| {
+            let int = context.inspect(context.get_argument(0));
+            let int: Integer = *int.as_ref().downcast_ref().unwrap();
+            native_record.lock().unwrap().push(int.0);
+        });
+        run(
+            registry,
+            "
+                .module main
+                .constant integer 0
+                .constant integer 1
+                .constant integer 3
+                .symbol start
+                    operate 0 push_count
+                    operate 1 generator.new
+                    operate 0 push_consume
+                    call 1
+                    return 0
+                .symbol consume
+                    assert_floating 1
+                    copy 1
+                    resume 0
+                    jump_unless done
+                    assert_floating 1
+                    operate 1 record
+                    copy 2
+                    operate 0 push_consume
+                    tail_call 1
+                done:
+                    copy 1
+                    resume 0
+                    jump_if resumed
+                    return 0
+                resumed:
+                    load_constant 0
+                    throw
+                .symbol count
+                    load_constant 0         ; i
+                loop:
+                    load_constant 1
+                    operate 2 integer.add   ; i' 1 i
+                    store_local 0
+                    pop                     ; i'
+                    copy 1
+                    yield 1
+                    load_constant 2
+                    operate 2 integer.lt    ; ? 3 i
+                    swap
+                    pop
+                    jump_if loop
+                    return 0
+            ",
+        )
+        .unwrap();
+        assert_eq!(*record.lock().unwrap(), [1, 2, 3]);
+    }
+
+    #[test]
+    fn reject_invalid_resume() {
+        let source = "
+            .module main
+            .symbol start
+                operate 0 push_count
+                operate 1 generator.new
+                copy 1
+                copy 2
+                resume 1
+                return 0
+            .symbol count
+                assert_floating 1
+                copy 1
+                resume 0
+                return 0
+        ";
+        assert_eq!(run(registry(), source), Err(ErrorKind::GeneratorRunning));
+        let source = "
+            .module main
+            .constant integer 0
+            .symbol start
+                load_constant 0
+                resume 0
+                return 0
+            .symbol count
+                return 0
+        ";
+        assert_eq!(run(registry(), source), Err(ErrorKind::NotGenerator));
+        let source = "
+            .module main
+            .constant integer 0
+            .symbol start
+                load_constant 0
+                yield 1
+                return 0
+        ";
+        assert_eq!(
+            run(registry(), source),
+            Err(ErrorKind::YieldOutsideGenerator)
+        );
+    }
+
+    #[test]
+    fn new_from_non_dispatch() {
+        let source = "
+            .module main
+            .constant true
+            .symbol start
+                load_constant 0
+                operate 1 generator.new
+                return 0
+        ";
+        let mut interp = Interpreter::new();
+        interp.load_module(assemble(source).unwrap()).unwrap();
+        interp.push_call(dispatch("start"), 0).unwrap();
+        let mut collector = TestCollector::default();
+        let error = loop {
+            if let Err(error) = interp.step(&mut collector) {
+                break error;
+            }
+        };
+        let ErrorKind::Uncaught(exception) = error.kind else {
+            panic!("unexpected error {error}")
+        };
+        let exception = collector.inspect(exception);
+        assert!(exception.as_ref().is::<TypeMismatch>());
+    }
+
+    #[test]
+    fn reload_while_suspended() {
+        let source = "
+            .module main
+            .constant dispatch main once
+            .symbol start
+                load_constant 0
+                operate 1 generator.new
+                copy 1
+                resume 0
+                load_local 1
+                return 1
+            .symbol next
+                assert_floating 1
+                resume 0
+                return 1
+            .symbol once
+                yield 0
+                return 0
+        ";
+        let mut collector = TestCollector::default();
+        let mut run = |interp: &mut Interpreter, symbol, variable_list: &[Address]| {
+            for address in variable_list {
+                interp.push_variable(*address);
+            }
+            interp.push_call(dispatch(symbol), 0)?;
+            while interp.has_step() {
+                interp.step(&mut collector)?;
+            }
+            Ok::<_, InterpreterError>(interp.reset())
+        };
+        let mut interp = Interpreter::new();
+        interp.load_module(assemble(source).unwrap()).unwrap();
+        let generator = run(&mut interp, "start", &[]).unwrap()[0];
+        interp.load_module(assemble(source).unwrap()).unwrap();
+        assert_eq!(interp.unload_stale(&Default::default()), []);
+        // resumed with the version it yielded from
+        run(&mut interp, "next", &[generator]).unwrap();
+        assert_eq!(
+            interp.unload_stale(&Default::default()),
+            [(Name::new("main"), 0)]
+        );
+
+        // yielded by version 1 of the first interpreter
+        let generator = run(&mut interp, "start", &[]).unwrap()[0];
+        let mut other = Interpreter::new();
+        other.load_module(assemble(source).unwrap()).unwrap();
+        let error = run(&mut other, "next", &[generator]).unwrap_err();
+        assert_eq!(error.kind, ErrorKind::VersionUnloaded(Name::new("main"), 1));
+    }
+
+    #[test]
+    fn enumerate_suspended() {
+        let generator = Generator {
+            dispatch: dispatch("count"),
+            state: GeneratorState::Suspended {
+                frame: Frame {
+                    pointer: (Name::new("main"), 0),
+                    version: 0,
+                    base: 0,
+                    stack_size: 0,
+                    handler_list: Vec::new(),
+                    generator: Some((0, 1)),
+                    n_result: None,
+                },
+                variable_list: vec![(0, 2), (0, 3)],
+            },
+        };
+        let mut address_list = Vec::new();
+        generator.enumerate_reference(&mut |address| address_list.push(address));
+        assert_eq!(address_list, [(0, 2), (0, 3)]);
+    }
+}
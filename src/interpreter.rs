@@ -1,44 +1,455 @@
-use crate::collector::{Address, Owned, Shared};
-use crate::objects::{Dispatch, False, List, True};
+use crate::collector::{Address, CollectorId, Owned, Shared, CONSTANT_HEAP_ID};
+use crate::format::FormatError;
+use crate::name::Name;
+use crate::native::{Native, NativeId, NativeRegistry};
+use crate::objects::{
+    Dispatch, False, Float, Generator, GeneratorState, ImmutableConstant, Integer, List,
+    StackOverflow, True,
+};
+use crate::profiler::Profiler;
 use crate::runner::CollectorInterface;
-use std::collections::HashMap;
-use std::mem::take;
+use crate::tracer::{StepRecord, Tracer};
+use crate::verifier::{verify, VerifyError};
+use std::cell::RefCell;
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::mem::{replace, take};
+use std::path::Path;
+use std::time::Instant;
 
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum ByteCode {
     Copy(u8),
-    Operate(u8, Box<dyn Fn(&mut dyn OperateContext)>),
-    Jump(i8), // jump if stack top is true, by instruction offset
-    Call(u8), // push calling frame according to Dispatch on stack top
+    Operate(u8, NativeId), // resolved against native registry on loading
+    Jump(i8),              // jump if stack top is true, by instruction offset
+    Goto(i32),             // jump unconditionally
+    JumpIf(i32),           // pop stack top, jump if it is true
+    JumpUnless(i32),       // pop stack top, jump if it is false
+    Call(u8),              // push calling frame according to Dispatch on stack top
+    TailCall(u8),          // replace current frame, keeping only the arguments
     Return(u8),
     AssertFloating(u8), // assert number of floating variables
     PackFloating(u8),   // pack remaining variables into one single variable
     Unpack,             // unpack List on stack top
+    Try(i32),           // install exception handler at offset in current frame
+    EndTry,             // remove innermost handler of current frame
+    Throw,              // pop stack top and unwind to the innermost handler
+    Yield(u8),          // suspend current generator frame, passing variables to resumer
+    Resume(u8),         // continue Generator on stack top, followed by a True if it yielded
+    LoadConstant(u16),  // push constant of current module by index
+    LoadLocal(u8),      // push variable by index from the frame entry
+    StoreLocal(u8),     // pop stack top into variable by index from the frame entry
+    Pop,                // drop stack top
+    Swap,               // exchange the top two variables
+    Rotate(u8),         // move stack top under the next n - 1 variables
 }
 
-pub type ModuleId = String;
+// absolute offset of the instruction that control reaches when the jump is taken
+pub fn jump_target(offset: usize, instruction: &ByteCode) -> Option<isize> {
+    let relative = match instruction {
+        ByteCode::Jump(relative) => *relative as i32,
+        ByteCode::Goto(relative)
+        | ByteCode::JumpIf(relative)
+        | ByteCode::JumpUnless(relative)
+        | ByteCode::Try(relative) => *relative,
+        _ => return None,
+    };
+    Some(offset as isize + 1 + relative as isize)
+}
+
+pub type ModuleId = Name;
+pub type Version = u32; // counted per module id, from 0 for the first load
 
 pub trait OperateContext: CollectorInterface {
     fn get_argument(&self, index: u8) -> Address;
     fn set_argument(&mut self, index: u8, address: Address);
     fn push_result(&mut self, address: Address);
+    // unwind to guest handler after native returns, results pushed are discarded
+    fn throw(&mut self, exception: Address);
+    // suspend the execution after native returns, results are kept. returns waker of
+    // current task if host provides one
+    fn suspend(&mut self) -> Option<Waker>;
 }
 
+pub type Waker = Box<dyn FnOnce() + Send>;
+
+#[derive(Debug, Clone, Default, PartialEq)]
 pub struct Module {
     pub id: ModuleId,
     pub program: Vec<ByteCode>,
-    pub symbol_table: HashMap<String, usize>,
+    pub symbol_table: HashMap<Name, usize>,
+    pub signature_table: HashMap<Name, Signature>, // of the symbols declaring one
+    pub import_list: Vec<(ModuleId, Name)>,        // checked by `Interpreter::link`
+    pub export_set: HashSet<Name>,                 // symbols callable from other modules
+    pub constant_list: Vec<Constant>,              // indexed by `LoadConstant`
+}
+
+// checked on calls into the symbol and on returns from the frame it enters
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Signature {
+    pub n_parameter: u8,
+    pub n_result: u8,
+}
+
+// immutable object allocated once per module load, shared by every task
+#[derive(Debug, Clone, PartialEq)]
+pub enum Constant {
+    True,
+    False,
+    Dispatch(Dispatch),
+    Integer(i64),
+    Float(f64),
+}
+
+impl Constant {
+    pub fn to_object(&self) -> Owned {
+        match self {
+            Constant::True => True.into(),
+            Constant::False => False.into(),
+            Constant::Dispatch(dispatch) => (*dispatch).into(),
+            Constant::Integer(value) => Integer(*value).into(),
+            Constant::Float(value) => Float(*value).into(),
+        }
+    }
+}
+
+impl Module {
+    pub fn nearest_symbol(&self, offset: usize) -> Option<Name> {
+        self.symbol_table
+            .iter()
+            .filter(|(_, symbol_offset)| **symbol_offset <= offset)
+            .max_by_key(|(symbol, symbol_offset)| (**symbol_offset, Reverse(*symbol)))
+            .map(|(symbol, _)| *symbol)
+    }
+}
+
+#[derive(Debug)]
+pub enum LoadError {
+    Format(FormatError),
+    UnknownNative {
+        module_id: ModuleId,
+        offset: usize,
+        native_id: NativeId,
+    },
+    Verify(VerifyError),
+    UnknownExport {
+        module_id: ModuleId,
+        symbol: Name,
+    },
+    UnknownSignature {
+        module_id: ModuleId,
+        symbol: Name,
+    },
+    KindConflict(ModuleId), // host and bytecode modules cannot share an id
+}
+
+impl Display for LoadError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            LoadError::Format(error) => write!(f, "{error}"),
+            LoadError::UnknownNative {
+                module_id,
+                offset,
+                native_id,
+            } => write!(f, "{module_id}:{offset}: unknown native {native_id}"),
+            LoadError::Verify(error) => write!(f, "{error}"),
+            LoadError::UnknownExport { module_id, symbol } => {
+                write!(f, "{module_id}: export unknown symbol {symbol}")
+            }
+            LoadError::UnknownSignature { module_id, symbol } => {
+                write!(f, "{module_id}: signature of unknown symbol {symbol}")
+            }
+            LoadError::KindConflict(module_id) => {
+                write!(f, "{module_id}: host and bytecode modules share the id")
+            }
+        }
+    }
+}
+
+impl Error for LoadError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            LoadError::Format(error) => Some(error),
+            LoadError::Verify(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<FormatError> for LoadError {
+    fn from(error: FormatError) -> Self {
+        Self::Format(error)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InterpreterError {
+    pub pointer: Option<(ModuleId, usize)>, // faulting instruction, if any
+    pub kind: ErrorKind,
+    pub backtrace: Vec<TraceEntry>, // innermost frame first
 }
 
-#[derive(Default)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceEntry {
+    pub module_id: ModuleId,
+    pub symbol: Option<Name>, // nearest symbol at or before offset
+    pub offset: usize,
+}
+
+impl Display for TraceEntry {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let symbol = self.symbol.as_deref().unwrap_or("?");
+        write!(f, "{symbol} ({}:{})", self.module_id, self.offset)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ErrorKind {
+    NoFrame,
+    OutOfProgram,
+    UnknownModule(ModuleId),
+    VersionUnloaded(ModuleId, Version), // entered by a generator resumed after unloading
+    UnknownSymbol(ModuleId, Name),
+    NotExported(ModuleId, Name),
+    NotGuest(ModuleId, Name),
+    NoHandler,
+    Uncaught(Address),
+    NotDispatch,
+    NotGenerator,
+    GeneratorRunning,
+    YieldOutsideGenerator,
+    NotBoolean,
+    NotList,
+    StackUnderflow,
+    StackOverflow, // exceeding `Limits` with no handler to catch it
+    FloatingMismatch {
+        expected: usize,
+        actual: usize,
+    },
+    ArgumentMismatch {
+        expected: usize,
+        actual: usize,
+    },
+    ReturnMismatch {
+        expected: usize,
+        actual: usize,
+    },
+    ResultMismatch {
+        native_id: NativeId,
+        expected: usize,
+        actual: usize,
+    },
+}
+
+impl Display for InterpreterError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        if let Some((module_id, offset)) = &self.pointer {
+            write!(f, "{module_id}:{offset}: ")?;
+        }
+        write!(f, "{}", self.kind)?;
+        for entry in &self.backtrace {
+            write!(f, "\n    at {entry}")?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for ErrorKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ErrorKind::NoFrame => write!(f, "no frame to step"),
+            ErrorKind::OutOfProgram => write!(f, "instruction pointer out of program"),
+            ErrorKind::UnknownModule(module_id) => write!(f, "unknown module {module_id}"),
+            ErrorKind::VersionUnloaded(module_id, version) => {
+                write!(f, "version {version} of module {module_id} is unloaded")
+            }
+            ErrorKind::NoHandler => write!(f, "no exception handler to remove"),
+            ErrorKind::Uncaught(address) => write!(f, "uncaught exception {address:?}"),
+            ErrorKind::NotExported(module_id, symbol) => {
+                write!(f, "symbol {symbol} is not exported by module {module_id}")
+            }
+            ErrorKind::NotGuest(module_id, symbol) => {
+                write!(f, "symbol {symbol} of host module {module_id} has no frame")
+            }
+            ErrorKind::UnknownSymbol(module_id, symbol) => {
+                write!(f, "unknown symbol {symbol} in module {module_id}")
+            }
+            ErrorKind::NotDispatch => write!(f, "call on non-dispatch variable"),
+            ErrorKind::NotGenerator => write!(f, "resume on non-generator variable"),
+            ErrorKind::GeneratorRunning => write!(f, "resume on running generator"),
+            ErrorKind::YieldOutsideGenerator => write!(f, "yield outside generator frame"),
+            ErrorKind::NotBoolean => write!(f, "jump on non-boolean variable"),
+            ErrorKind::NotList => write!(f, "unpack on non-list variable"),
+            ErrorKind::StackUnderflow => write!(f, "variable stack underflow"),
+            ErrorKind::FloatingMismatch { expected, actual } => {
+                write!(f, "expect {expected} floating variables, found {actual}")
+            }
+            ErrorKind::StackOverflow => write!(f, "stack overflow"),
+            ErrorKind::ArgumentMismatch { expected, actual } => {
+                write!(f, "expect {expected} arguments, called with {actual}")
+            }
+            ErrorKind::ReturnMismatch { expected, actual } => {
+                write!(f, "expect {expected} results, returned {actual}")
+            }
+            ErrorKind::ResultMismatch {
+                native_id,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "native {native_id} expect {expected} results, pushed {actual}"
+            ),
+        }
+    }
+}
+
+impl Error for InterpreterError {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LinkError {
+    pub unresolved_list: Vec<UnresolvedImport>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnresolvedImport {
+    pub module_id: ModuleId, // importing module
+    pub import: (ModuleId, Name),
+    pub kind: UnresolvedKind,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnresolvedKind {
+    UnknownModule,
+    UnknownSymbol,
+    NotExported,
+}
+
+impl Display for LinkError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{} unresolved imports", self.unresolved_list.len())?;
+        for unresolved in &self.unresolved_list {
+            let (module_id, symbol) = &unresolved.import;
+            write!(f, "\n    {}: {module_id}.{symbol} ", unresolved.module_id)?;
+            match unresolved.kind {
+                UnresolvedKind::UnknownModule => write!(f, "from unknown module"),
+                UnresolvedKind::UnknownSymbol => write!(f, "is unknown"),
+                UnresolvedKind::NotExported => write!(f, "is not exported"),
+            }?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for LinkError {}
+
 pub struct Interpreter {
-    module_table: HashMap<ModuleId, Module>,
+    native_registry: NativeRegistry,
+    // replaced versions are kept for the frames still running them
+    module_table: HashMap<ModuleId, BTreeMap<Version, LoadedModule>>,
+    host_module_set: HashSet<ModuleId>,
+    dispatch_table: HashMap<Dispatch, Resolved>, // every symbol of current module versions
+    variable_stack: Vec<Address>,
+    call_stack: Vec<Frame>,
+    // frames yielded into generators, until resumed. kept for generators that are never
+    // resumed again, as they are not tracked by collector
+    generator_version_table: BTreeMap<(ModuleId, Version), usize>,
+    profiler: Option<Profiler>,
+    tracer: Option<Tracer>,
+    waker: Option<Waker>, // handed to the native that suspends
+    suspended: bool,      // by the last step
+    limits: Limits,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Limits {
+    pub max_call_depth: usize, // frames on call stack, including host entry
+    pub max_stack_size: usize, // variables of all frames
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            max_call_depth: 10000,
+            max_stack_size: 1 << 20,
+        }
+    }
+}
+
+struct LoadedModule {
+    module: Module,
+    native_list: Vec<Option<Native>>, // indexed by instruction offset
+    // on the first `LoadConstant` of this version with each collector, since loading has
+    // no collector. the constants stay in the collectors after the version is unloaded
+    constant_address_table: RefCell<HashMap<CollectorId, Vec<Address>>>,
+}
+
+// pointer, version and signature of a guest symbol
+type Entry = ((ModuleId, usize), Version, Option<Signature>);
+
+#[derive(Clone)]
+enum Resolved {
+    Guest {
+        offset: usize,
+        version: Version,
+        exported: bool,
+        signature: Option<Signature>,
+    },
+    Host(Native), // always exported
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame {
+    pub pointer: (ModuleId, usize), // next instruction to be stepped
+    pub version: Version,           // module version the pointer refers to
+    pub base: usize,                // variable stack length below the arguments on entry
+    pub stack_size: usize,
+    pub handler_list: Vec<Handler>, // innermost last
+    pub generator: Option<Address>, // resumed by the caller if any
+    pub n_result: Option<u8>,       // declared by the entered symbol, checked on return
+}
+
+// state of a frame restored when an exception is caught by it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Handler {
+    pub offset: usize,
+    pub stack_size: usize,
+    pub stack_len: usize, // variable stack length on installing
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RunStatus {
+    Finished,
+    OutOfFuel, // stepped as many instructions as fuel, execution can be continued
+    Suspended, // by native, execution can be continued after task is woken
+}
+
+// interrupted execution taken out of an interpreter, to be resumed later
+pub struct Continuation {
     variable_stack: Vec<Address>,
     call_stack: Vec<Frame>,
 }
 
-struct Frame {
-    pointer: (ModuleId, usize),
-    stack_size: usize,
+impl Continuation {
+    pub fn versions_in_use(&self) -> BTreeSet<(ModuleId, Version)> {
+        versions_in_use(&self.call_stack)
+    }
+}
+
+fn versions_in_use(call_stack: &[Frame]) -> BTreeSet<(ModuleId, Version)> {
+    call_stack
+        .iter()
+        .map(|frame| (frame.pointer.0, frame.version))
+        .collect()
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Self::with_registry(NativeRegistry::standard())
+    }
 }
 
 impl Interpreter {
@@ -46,22 +457,363 @@ impl Interpreter {
         Self::default()
     }
 
-    pub fn load_module(&mut self, module: Module) {
-        self.module_table.insert(module.id.clone(), module);
+    pub fn with_registry(native_registry: NativeRegistry) -> Self {
+        Self {
+            native_registry,
+            module_table: Default::default(),
+            host_module_set: Default::default(),
+            dispatch_table: Default::default(),
+            variable_stack: Default::default(),
+            call_stack: Default::default(),
+            generator_version_table: Default::default(),
+            profiler: None,
+            tracer: None,
+            waker: None,
+            suspended: false,
+            limits: Default::default(),
+        }
     }
 
-    pub fn push_call(&mut self, dispatch: Dispatch, stack_size: usize) {
-        let offset = *self
-            .module_table
-            .get(&dispatch.module_id)
-            .unwrap()
-            .symbol_table
-            .get(&dispatch.symbol)
-            .unwrap();
+    // checked on following steps, stacks already beyond the new limits are left as is
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.limits = limits;
+    }
+
+    pub fn limits(&self) -> Limits {
+        self.limits
+    }
+
+    // waker of the task going to be run
+    pub fn set_waker(&mut self, waker: Option<Waker>) {
+        self.waker = waker;
+    }
+
+    pub fn set_tracer(&mut self, tracer: Option<Tracer>) -> Option<Tracer> {
+        replace(&mut self.tracer, tracer)
+    }
+
+    // replace the profiler recording following steps, returning the previous one
+    pub fn set_profiler(&mut self, profiler: Option<Profiler>) -> Option<Profiler> {
+        replace(&mut self.profiler, profiler)
+    }
+
+    pub fn profiler(&self) -> Option<&Profiler> {
+        self.profiler.as_ref()
+    }
+
+    pub fn registry_mut(&mut self) -> &mut NativeRegistry {
+        &mut self.native_registry
+    }
+
+    pub fn load_module(&mut self, module: Module) -> Result<(), LoadError> {
+        let mut native_list = Vec::with_capacity(module.program.len());
+        for (offset, instruction) in module.program.iter().enumerate() {
+            native_list.push(if let ByteCode::Operate(_, native_id) = instruction {
+                let native = self.native_registry.get(native_id).ok_or_else(|| {
+                    LoadError::UnknownNative {
+                        module_id: module.id,
+                        offset,
+                        native_id: native_id.clone(),
+                    }
+                })?;
+                Some(native.clone())
+            } else {
+                None
+            });
+        }
+        if self.host_module_set.contains(&module.id) {
+            return Err(LoadError::KindConflict(module.id));
+        }
+        verify(&module, &self.native_registry).map_err(LoadError::Verify)?;
+        let mut export_list: Vec<_> = module.export_set.iter().collect();
+        export_list.sort();
+        for symbol in export_list {
+            if !module.symbol_table.contains_key(symbol) {
+                return Err(LoadError::UnknownExport {
+                    module_id: module.id,
+                    symbol: *symbol,
+                });
+            }
+        }
+        let mut signature_list: Vec<_> = module.signature_table.keys().collect();
+        signature_list.sort();
+        for symbol in signature_list {
+            if !module.symbol_table.contains_key(symbol) {
+                return Err(LoadError::UnknownSignature {
+                    module_id: module.id,
+                    symbol: *symbol,
+                });
+            }
+        }
+        // running frames keep the version they entered, new calls go to this one
+        let loaded_table = self.module_table.entry(module.id).or_default();
+        let version = match loaded_table.last_key_value() {
+            Some((version, _)) => version + 1,
+            None => 0,
+        };
+        self.dispatch_table
+            .retain(|dispatch, _| dispatch.module_id != module.id);
+        for (symbol, offset) in &module.symbol_table {
+            let dispatch = Dispatch {
+                module_id: module.id,
+                symbol: *symbol,
+            };
+            let resolved = Resolved::Guest {
+                offset: *offset,
+                version,
+                exported: module.export_set.contains(symbol),
+                signature: module.signature_table.get(symbol).copied(),
+            };
+            self.dispatch_table.insert(dispatch, resolved);
+        }
+        loaded_table.insert(
+            version,
+            LoadedModule {
+                module,
+                native_list,
+                constant_address_table: Default::default(),
+            },
+        );
+        Ok(())
+    }
+
+    // symbols of a host module are natives invoked by `Call` with the arguments of the call,
+    // their results are returned as from a bytecode function. loading again replaces it
+    pub fn load_host_module(
+        &mut self,
+        module_id: ModuleId,
+        registry: &NativeRegistry,
+    ) -> Result<(), LoadError> {
+        if self.module_table.contains_key(&module_id) {
+            return Err(LoadError::KindConflict(module_id));
+        }
+        self.host_module_set.insert(module_id);
+        self.dispatch_table
+            .retain(|dispatch, _| dispatch.module_id != module_id);
+        for (native_id, native) in registry.natives() {
+            let dispatch = Dispatch {
+                module_id,
+                symbol: Name::new(native_id),
+            };
+            self.dispatch_table
+                .insert(dispatch, Resolved::Host(native.clone()));
+        }
+        Ok(())
+    }
+
+    pub fn current_version(&self, module_id: ModuleId) -> Option<Version> {
+        let loaded_table = self.module_table.get(&module_id)?;
+        loaded_table.last_key_value().map(|(version, _)| *version)
+    }
+
+    // versions entered by frames on the call stack
+    pub fn versions_in_use(&self) -> BTreeSet<(ModuleId, Version)> {
+        versions_in_use(&self.call_stack)
+    }
+
+    // drop replaced versions that no frame or suspended generator is running, returning the
+    // dropped ones. versions in use by suspended `Continuation`s must be retained by caller
+    pub fn unload_stale(
+        &mut self,
+        retained: &BTreeSet<(ModuleId, Version)>,
+    ) -> Vec<(ModuleId, Version)> {
+        let in_use = self.versions_in_use();
+        let generator_version_table = &self.generator_version_table;
+        let mut unloaded_list = Vec::new();
+        for (module_id, loaded_table) in &mut self.module_table {
+            let current = *loaded_table.last_key_value().unwrap().0;
+            loaded_table.retain(|version, _| {
+                let pointer = (*module_id, *version);
+                let retain = *version == current
+                    || in_use.contains(&pointer)
+                    || generator_version_table.contains_key(&pointer)
+                    || retained.contains(&pointer);
+                if !retain {
+                    unloaded_list.push(pointer);
+                }
+                retain
+            });
+        }
+        unloaded_list.sort();
+        unloaded_list
+    }
+
+    fn loaded(&self, module_id: ModuleId, version: Version) -> &LoadedModule {
+        &self.module_table[&module_id][&version]
+    }
+
+    pub fn load_from<R: Read>(&mut self, reader: &mut R) -> Result<(), LoadError> {
+        self.load_module(Module::read_from(reader)?)
+    }
+
+    pub fn load_file(&mut self, path: impl AsRef<Path>) -> Result<(), LoadError> {
+        let file = File::open(path).map_err(FormatError::Io)?;
+        self.load_from(&mut BufReader::new(file))
+    }
+
+    pub fn modules(&self) -> impl Iterator<Item = &Module> {
+        self.module_table
+            .values()
+            .map(|loaded_table| &loaded_table.last_key_value().unwrap().1.module)
+    }
+
+    // check imports of all loaded modules, reporting every unresolved one
+    pub fn link(&self) -> Result<(), LinkError> {
+        let mut module_list: Vec<_> = self.modules().collect();
+        module_list.sort_by_key(|module| module.id);
+        let mut unresolved_list = Vec::new();
+        for module in module_list {
+            for import in &module.import_list {
+                let kind = match self.module(import.0) {
+                    None if self.host_module_set.contains(&import.0) => {
+                        let dispatch = Dispatch {
+                            module_id: import.0,
+                            symbol: import.1,
+                        };
+                        if self.dispatch_table.contains_key(&dispatch) {
+                            continue;
+                        }
+                        UnresolvedKind::UnknownSymbol
+                    }
+                    None => UnresolvedKind::UnknownModule,
+                    Some(imported) if !imported.symbol_table.contains_key(&import.1) => {
+                        UnresolvedKind::UnknownSymbol
+                    }
+                    Some(imported)
+                        if imported.id != module.id && !imported.export_set.contains(&import.1) =>
+                    {
+                        UnresolvedKind::NotExported
+                    }
+                    Some(_) => continue,
+                };
+                unresolved_list.push(UnresolvedImport {
+                    module_id: module.id,
+                    import: *import,
+                    kind,
+                });
+            }
+        }
+        if unresolved_list.is_empty() {
+            Ok(())
+        } else {
+            Err(LinkError { unresolved_list })
+        }
+    }
+
+    // current version of the module
+    pub fn module(&self, module_id: ModuleId) -> Option<&Module> {
+        let loaded_table = self.module_table.get(&module_id)?;
+        loaded_table
+            .last_key_value()
+            .map(|(_, loaded)| &loaded.module)
+    }
+
+    // outermost frame first
+    pub fn call_stack(&self) -> &[Frame] {
+        &self.call_stack
+    }
+
+    pub fn variable_stack(&self) -> &[Address] {
+        &self.variable_stack
+    }
+
+    pub fn push_call(
+        &mut self,
+        dispatch: Dispatch,
+        stack_size: usize,
+    ) -> Result<(), InterpreterError> {
+        let (pointer, version, signature) = self.resolve(dispatch, false)?;
+        let Some(n_argument) = self.variable_stack.len().checked_sub(stack_size) else {
+            return Err(self.fault(ErrorKind::StackUnderflow));
+        };
+        self.check_arguments(signature, n_argument)?;
+        if self.call_stack.len() >= self.limits.max_call_depth {
+            return Err(self.fault(ErrorKind::StackOverflow));
+        }
         self.call_stack.push(Frame {
-            pointer: (dispatch.module_id, offset),
+            pointer,
+            version,
+            base: stack_size,
             stack_size,
+            handler_list: Vec::new(),
+            generator: None,
+            n_result: signature.map(|signature| signature.n_result),
         });
+        Ok(())
+    }
+
+    // calls from guest code may only cross modules through exported symbols
+    fn lookup(&self, dispatch: Dispatch, from_guest: bool) -> Result<&Resolved, InterpreterError> {
+        let Some(resolved) = self.dispatch_table.get(&dispatch) else {
+            let kind = if self.module_table.contains_key(&dispatch.module_id)
+                || self.host_module_set.contains(&dispatch.module_id)
+            {
+                ErrorKind::UnknownSymbol(dispatch.module_id, dispatch.symbol)
+            } else {
+                ErrorKind::UnknownModule(dispatch.module_id)
+            };
+            return Err(self.fault(kind));
+        };
+        let caller = self.call_stack.last().map(|frame| frame.pointer.0);
+        if from_guest
+            && caller != Some(dispatch.module_id)
+            && matches!(
+                resolved,
+                Resolved::Guest {
+                    exported: false,
+                    ..
+                }
+            )
+        {
+            return Err(self.fault(ErrorKind::NotExported(dispatch.module_id, dispatch.symbol)));
+        }
+        Ok(resolved)
+    }
+
+    fn resolve(&self, dispatch: Dispatch, from_guest: bool) -> Result<Entry, InterpreterError> {
+        match self.lookup(dispatch, from_guest)? {
+            Resolved::Guest {
+                offset,
+                version,
+                signature,
+                ..
+            } => Ok(((dispatch.module_id, *offset), *version, *signature)),
+            Resolved::Host(_) => {
+                Err(self.fault(ErrorKind::NotGuest(dispatch.module_id, dispatch.symbol)))
+            }
+        }
+    }
+
+    fn check_arguments(
+        &self,
+        signature: Option<Signature>,
+        n_argument: usize,
+    ) -> Result<(), InterpreterError> {
+        match signature {
+            Some(signature) if signature.n_parameter as usize != n_argument => {
+                Err(self.fault(ErrorKind::ArgumentMismatch {
+                    expected: signature.n_parameter as usize,
+                    actual: n_argument,
+                }))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn check_results(
+        &self,
+        n_result: Option<u8>,
+        n_returned: usize,
+    ) -> Result<(), InterpreterError> {
+        match n_result {
+            Some(n_result) if n_result as usize != n_returned => {
+                Err(self.fault(ErrorKind::ReturnMismatch {
+                    expected: n_result as usize,
+                    actual: n_returned,
+                }))
+            }
+            _ => Ok(()),
+        }
     }
 
     pub fn has_step(&self) -> bool {
@@ -77,12 +829,85 @@ impl Interpreter {
         assert!(!self.has_step(), "stack is not free");
         self.variable_stack.push(address);
     }
+
+    pub fn suspend(&mut self) -> Continuation {
+        Continuation {
+            variable_stack: take(&mut self.variable_stack),
+            call_stack: take(&mut self.call_stack),
+        }
+    }
+
+    pub fn resume(&mut self, continuation: Continuation) {
+        assert!(
+            !self.has_step() && self.variable_stack.is_empty(),
+            "stack is not free"
+        );
+        self.variable_stack = continuation.variable_stack;
+        self.call_stack = continuation.call_stack;
+    }
+
+    // discard a faulted execution, returning the variables left on stack
+    pub fn abort(&mut self) -> Vec<Address> {
+        self.call_stack.clear();
+        take(&mut self.variable_stack)
+    }
+
+    // innermost frame first, caller frames are located at their calling instruction
+    pub fn backtrace(&self) -> Vec<TraceEntry> {
+        self.trace(0)
+    }
+
+    fn trace(&self, top_rewind: usize) -> Vec<TraceEntry> {
+        self.call_stack
+            .iter()
+            .rev()
+            .enumerate()
+            .map(|(index, frame)| {
+                let rewind = if index == 0 { top_rewind } else { 1 };
+                self.trace_entry(frame, stepped_offset(frame, rewind))
+            })
+            .collect()
+    }
+
+    fn trace_entry(&self, frame: &Frame, offset: usize) -> TraceEntry {
+        let module_id = frame.pointer.0;
+        let symbol = self
+            .loaded(module_id, frame.version)
+            .module
+            .nearest_symbol(offset);
+        TraceEntry {
+            module_id,
+            symbol,
+            offset,
+        }
+    }
+
+    // error located at the instruction being stepped, whose offset is already advanced
+    fn fault(&self, kind: ErrorKind) -> InterpreterError {
+        InterpreterError {
+            pointer: self
+                .call_stack
+                .last()
+                .map(|frame| (frame.pointer.0, stepped_offset(frame, 1))),
+            kind,
+            backtrace: self.trace(1),
+        }
+    }
+}
+
+// frames pushed by host have not stepped any instruction, and the one at offset 0 has
+// nothing to rewind to
+fn stepped_offset(frame: &Frame, rewind: usize) -> usize {
+    frame.pointer.1.saturating_sub(rewind)
 }
 
 struct OperateView<'i> {
     collector: &'i mut dyn CollectorInterface,
     variable_stack: &'i mut Vec<Address>,
     argument_offset: usize,
+    thrown: Option<Address>,
+    waker: &'i mut Option<Waker>,
+    suspended: bool,
 }
 
 impl<'i> CollectorInterface for OperateView<'i> {
@@ -92,9 +917,19 @@ impl<'i> CollectorInterface for OperateView<'i> {
     fn inspect(&self, address: Address) -> Shared {
         self.collector.inspect(address)
     }
+    // a constant is left as is and the native throws after it returns, getting its own
+    // object back
     fn replace(&mut self, address: Address, owned: Owned) -> Owned {
+        if address.0 == CONSTANT_HEAP_ID {
+            let error = self.collector.allocate(ImmutableConstant.into());
+            self.thrown = Some(error);
+            return owned;
+        }
         self.collector.replace(address, owned)
     }
+    fn collector_id(&self) -> CollectorId {
+        self.collector.collector_id()
+    }
 }
 
 impl<'i> OperateContext for OperateView<'i> {
@@ -107,6 +942,13 @@ impl<'i> OperateContext for OperateView<'i> {
     fn push_result(&mut self, address: Address) {
         self.variable_stack.push(address);
     }
+    fn throw(&mut self, exception: Address) {
+        self.thrown = Some(exception);
+    }
+    fn suspend(&mut self) -> Option<Waker> {
+        self.suspended = true;
+        self.waker.take()
+    }
 }
 
 pub trait StepContext {
@@ -115,85 +957,617 @@ pub trait StepContext {
 }
 
 impl Interpreter {
-    pub fn step(&mut self, collector: &mut dyn CollectorInterface) {
-        let pointer = &mut self.call_stack.last_mut().unwrap().pointer;
-        let instruction = &self.module_table.get(&pointer.0).unwrap().program[pointer.1];
+    pub fn run(
+        &mut self,
+        collector: &mut dyn CollectorInterface,
+        fuel: usize,
+    ) -> Result<RunStatus, InterpreterError> {
+        for _ in 0..fuel {
+            if !self.has_step() {
+                return Ok(RunStatus::Finished);
+            }
+            self.step(collector)?;
+            if self.suspended {
+                return Ok(RunStatus::Suspended);
+            }
+        }
+        Ok(if self.has_step() {
+            RunStatus::OutOfFuel
+        } else {
+            RunStatus::Finished
+        })
+    }
+
+    pub fn step(&mut self, collector: &mut dyn CollectorInterface) -> Result<(), InterpreterError> {
+        let Some(frame) = self.call_stack.last() else {
+            return self.step_instruction(collector);
+        };
+        if self.profiler.is_none() && self.tracer.is_none() {
+            return self.step_instruction(collector);
+        }
+        let entry = self
+            .profiler
+            .as_ref()
+            .map(|_| self.trace_entry(frame, frame.pointer.1));
+        let backtrace = match &self.profiler {
+            Some(profiler) if profiler.sample_due() => Some(self.backtrace()),
+            _ => None,
+        };
+        let pointer = frame.pointer;
+        let program = &self.loaded(pointer.0, frame.version).module.program;
+        let kind = program.get(pointer.1).map_or("", ByteCode::mnemonic);
+        let frame_depth = self.call_stack.len();
+        let stack_before = self.tracer.as_ref().map(|_| self.variable_stack.clone());
+
+        let start = Instant::now();
+        let result = self.step_instruction(collector);
+        let time = start.elapsed();
+
+        if let (Some(profiler), Some(entry)) = (&mut self.profiler, entry) {
+            profiler.record_step(&entry, time, backtrace);
+        }
+        if let (Some(tracer), Some(stack_before)) = (&mut self.tracer, stack_before) {
+            // stacks are left as is on fault
+            let stack_after = match &result {
+                Ok(()) => &self.variable_stack[..],
+                Err(_) => &stack_before[..],
+            };
+            tracer.record(StepRecord {
+                pointer: &pointer,
+                kind,
+                stack_before: &stack_before,
+                stack_after,
+                frame_depth,
+                error: result.as_ref().err(),
+            });
+        }
+        result
+    }
+
+    fn step_instruction(
+        &mut self,
+        collector: &mut dyn CollectorInterface,
+    ) -> Result<(), InterpreterError> {
+        let stack_len = self.variable_stack.len();
+        self.execute(collector)?;
+        // instructions pushing frames do not grow variable stack except `Resume`, which
+        // checks by itself, so the overflow is raised in the frame of the instruction
+        let len = self.variable_stack.len();
+        if len > stack_len && len > self.limits.max_stack_size {
+            return self.overflow(collector);
+        }
+        Ok(())
+    }
+
+    fn execute(&mut self, collector: &mut dyn CollectorInterface) -> Result<(), InterpreterError> {
+        self.suspended = false;
+        let Some(frame) = self.call_stack.last_mut() else {
+            return Err(self.fault(ErrorKind::NoFrame));
+        };
+        let pointer = &mut frame.pointer;
+        let (base, stack_size) = (frame.base, frame.stack_size);
+        let loaded = &self.module_table[&pointer.0][&frame.version];
+        let Some(instruction) = loaded.module.program.get(pointer.1) else {
+            let pointer = Some(*pointer);
+            return Err(InterpreterError {
+                pointer,
+                kind: ErrorKind::OutOfProgram,
+                backtrace: self.backtrace(),
+            });
+        };
+        let native = &loaded.native_list[pointer.1];
         pointer.1 += 1;
+        let n_floating = self.variable_stack.len() - stack_size;
         match instruction {
             ByteCode::Copy(offset) => {
+                let offset = *offset as usize;
+                if offset == 0 || offset > self.variable_stack.len() {
+                    return Err(self.fault(ErrorKind::StackUnderflow));
+                }
                 self.variable_stack
-                    .push(self.variable_stack[self.variable_stack.len() - *offset as usize]);
+                    .push(self.variable_stack[self.variable_stack.len() - offset]);
             }
-            ByteCode::Operate(n_argument, op) => {
-                let argument_offset = self.variable_stack.len() - *n_argument as usize;
-                op(&mut OperateView {
+            ByteCode::LoadLocal(index) => {
+                let slot = base + *index as usize;
+                if slot >= self.variable_stack.len() {
+                    return Err(self.fault(ErrorKind::StackUnderflow));
+                }
+                self.variable_stack.push(self.variable_stack[slot]);
+            }
+            ByteCode::StoreLocal(index) => {
+                let slot = base + *index as usize;
+                if n_floating == 0 || slot + 1 >= self.variable_stack.len() {
+                    return Err(self.fault(ErrorKind::StackUnderflow));
+                }
+                self.variable_stack[slot] = self.variable_stack.pop().unwrap();
+            }
+            ByteCode::Pop => {
+                if n_floating == 0 {
+                    return Err(self.fault(ErrorKind::StackUnderflow));
+                }
+                self.variable_stack.pop();
+            }
+            ByteCode::Swap => {
+                if n_floating < 2 {
+                    return Err(self.fault(ErrorKind::StackUnderflow));
+                }
+                let len = self.variable_stack.len();
+                self.variable_stack.swap(len - 1, len - 2);
+            }
+            ByteCode::Rotate(n) => {
+                let n = *n as usize;
+                if n_floating < n {
+                    return Err(self.fault(ErrorKind::StackUnderflow));
+                }
+                // rejected by verifier, nothing to rotate anyway
+                if n > 0 {
+                    let len = self.variable_stack.len();
+                    self.variable_stack[len - n..].rotate_right(1);
+                }
+            }
+            ByteCode::Operate(n_argument, native_id) => {
+                let Some(argument_offset) =
+                    self.variable_stack.len().checked_sub(*n_argument as usize)
+                else {
+                    return Err(self.fault(ErrorKind::StackUnderflow));
+                };
+                let native = native.as_ref().unwrap();
+                if let Some(profiler) = &mut self.profiler {
+                    profiler.record_native(native_id);
+                }
+                let outcome = run_native(
+                    native,
                     collector,
-                    variable_stack: &mut self.variable_stack,
+                    &mut self.variable_stack,
+                    &mut self.waker,
                     argument_offset,
-                });
+                );
+                self.suspended = outcome.suspended;
+                if let Some(exception) = outcome.thrown {
+                    return self.throw(collector, exception);
+                }
+                if outcome.n_result != native.n_result as usize {
+                    let native_id = native_id.clone();
+                    return Err(self.fault(ErrorKind::ResultMismatch {
+                        native_id,
+                        expected: native.n_result as usize,
+                        actual: outcome.n_result,
+                    }));
+                }
             }
             ByteCode::Jump(offset) => {
-                let offset = *offset;
-                let top = *self.variable_stack.last().unwrap();
-                let top = collector.inspect(top);
-                if top.as_ref().is::<True>() {
-                    let pointer = &mut self.call_stack.last_mut().unwrap().pointer;
-                    if offset > 0 {
-                        pointer.1 += offset as usize;
-                    } else {
-                        pointer.1 -= (-offset) as usize;
-                    }
-                } else if !top.as_ref().is::<False>() {
-                    panic!("jump on non-boolean variable {:?}", &*top);
+                let offset = *offset as isize;
+                let Some(top) = self.variable_stack.last() else {
+                    return Err(self.fault(ErrorKind::StackUnderflow));
+                };
+                if self.test(collector, *top)? {
+                    self.jump(offset)?;
+                }
+            }
+            ByteCode::Goto(offset) => {
+                let offset = *offset as isize;
+                self.jump(offset)?;
+            }
+            ByteCode::JumpIf(offset) | ByteCode::JumpUnless(offset) => {
+                let (offset, expected) =
+                    (*offset as isize, matches!(instruction, ByteCode::JumpIf(_)));
+                if n_floating == 0 {
+                    return Err(self.fault(ErrorKind::StackUnderflow));
+                }
+                let top = self.variable_stack.pop().unwrap();
+                if self.test(collector, top)? == expected {
+                    self.jump(offset)?;
                 }
             }
             ByteCode::Call(n_argument) => {
+                if n_floating < *n_argument as usize + 1 {
+                    return Err(self.fault(ErrorKind::StackUnderflow));
+                }
                 let dispatch = collector.inspect(*self.variable_stack.last().unwrap());
-                let dispatch: &Dispatch = dispatch.as_ref().downcast_ref().unwrap();
-                let dispatch = dispatch.clone();
-                self.variable_stack.remove(self.variable_stack.len() - 1); // is it useful to save it?
+                let Some(dispatch) = dispatch.as_ref().downcast_ref::<Dispatch>() else {
+                    return Err(self.fault(ErrorKind::NotDispatch));
+                };
+                let dispatch = *dispatch;
+                let resolved = self.lookup(dispatch, true)?.clone();
+                if let Resolved::Guest { signature, .. } = resolved {
+                    self.check_arguments(signature, *n_argument as usize)?;
+                    if self.call_stack.len() >= self.limits.max_call_depth {
+                        return self.overflow(collector);
+                    }
+                }
+                self.variable_stack.pop(); // is it useful to save it?
                 let stack_size = self.variable_stack.len() - *n_argument as usize;
                 self.call_stack.last_mut().unwrap().stack_size = stack_size;
-                self.push_call(dispatch, stack_size);
+                match resolved {
+                    Resolved::Guest {
+                        offset,
+                        version,
+                        signature,
+                        ..
+                    } => self.call_stack.push(Frame {
+                        pointer: (dispatch.module_id, offset),
+                        version,
+                        base: stack_size,
+                        stack_size,
+                        handler_list: Vec::new(),
+                        generator: None,
+                        n_result: signature.map(|signature| signature.n_result),
+                    }),
+                    // results are left for the caller as if a frame returned
+                    Resolved::Host(native) => {
+                        self.call_host(collector, dispatch, &native, stack_size)?;
+                    }
+                }
             }
-            ByteCode::Return(n_returned) => {
-                let n_returned = *n_returned;
-                self.call_stack.pop();
-                let stack_size = self
-                    .call_stack
-                    .last()
-                    .map(|frame| frame.stack_size)
-                    .unwrap_or(0);
+            ByteCode::TailCall(n_argument) => {
+                let n_argument = *n_argument as usize;
+                if n_floating < n_argument + 1 {
+                    return Err(self.fault(ErrorKind::StackUnderflow));
+                }
+                let dispatch = collector.inspect(*self.variable_stack.last().unwrap());
+                let Some(dispatch) = dispatch.as_ref().downcast_ref::<Dispatch>() else {
+                    return Err(self.fault(ErrorKind::NotDispatch));
+                };
+                let dispatch = *dispatch;
+                // the caller still expects the results declared by current frame
+                let n_result = self.call_stack.last().unwrap().n_result;
+                let (pointer, version, signature) = match self.lookup(dispatch, true)? {
+                    Resolved::Guest {
+                        offset,
+                        version,
+                        signature,
+                        ..
+                    } => ((dispatch.module_id, *offset), *version, *signature),
+                    Resolved::Host(native) => {
+                        let native = native.clone();
+                        self.check_results(n_result, native.n_result as usize)?;
+                        self.variable_stack.pop();
+                        let argument_offset = self.variable_stack.len() - n_argument;
+                        if self.call_host(collector, dispatch, &native, argument_offset)? {
+                            self.return_frame(collector, native.n_result as usize);
+                        }
+                        return Ok(());
+                    }
+                };
+                self.check_arguments(signature, n_argument)?;
+                let callee_n_result = signature.map(|signature| signature.n_result);
+                if let Some(callee_n_result) = callee_n_result {
+                    self.check_results(n_result, callee_n_result as usize)?;
+                }
+                self.variable_stack.pop();
+                // arguments take the place of current frame's own ones
                 self.variable_stack
-                    .drain(stack_size..self.variable_stack.len() - n_returned as usize);
+                    .drain(base..self.variable_stack.len() - n_argument);
+                let frame = self.call_stack.last_mut().unwrap();
+                *frame = Frame {
+                    pointer,
+                    version,
+                    base,
+                    stack_size: base,
+                    handler_list: Vec::new(),
+                    generator: frame.generator,
+                    n_result: callee_n_result.or(n_result),
+                };
             }
-            ByteCode::AssertFloating(n_floating) => {
-                assert_eq!(
-                    self.variable_stack.len() - self.call_stack.last().unwrap().stack_size,
-                    *n_floating as usize
-                );
+            ByteCode::Return(n_returned) => {
+                let n_returned = *n_returned as usize;
+                if n_floating < n_returned {
+                    return Err(self.fault(ErrorKind::StackUnderflow));
+                }
+                self.check_results(self.call_stack.last().unwrap().n_result, n_returned)?;
+                self.return_frame(collector, n_returned);
+            }
+            ByteCode::AssertFloating(expected) => {
+                let expected = *expected as usize;
+                if n_floating != expected {
+                    return Err(self.fault(ErrorKind::FloatingMismatch {
+                        expected,
+                        actual: n_floating,
+                    }));
+                }
             }
             ByteCode::PackFloating(n_destructed) => {
-                let n_destructed = *n_destructed;
-                let stack_size = self.call_stack.last().unwrap().stack_size;
-                assert!(self.variable_stack.len() - stack_size >= n_destructed as usize);
-                let pack_offset = stack_size + n_destructed as usize;
-                let list = List((&self.variable_stack[pack_offset..]).to_vec());
+                let n_destructed = *n_destructed as usize;
+                if n_floating < n_destructed {
+                    return Err(self.fault(ErrorKind::StackUnderflow));
+                }
+                let pack_offset = stack_size + n_destructed;
+                let list = List(self.variable_stack[pack_offset..].to_vec());
                 let list = collector.allocate(list.into());
                 self.variable_stack.drain(pack_offset..);
                 self.variable_stack.push(list);
             }
+            ByteCode::LoadConstant(index) => {
+                let mut address_table = loaded.constant_address_table.borrow_mut();
+                let address_list = address_table
+                    .entry(collector.collector_id())
+                    .or_insert_with(|| {
+                        let constant_list = &loaded.module.constant_list;
+                        constant_list
+                            .iter()
+                            .map(|constant| collector.allocate_constant(constant.to_object()))
+                            .collect()
+                    });
+                // index is checked by verifier
+                self.variable_stack.push(address_list[*index as usize]);
+            }
             ByteCode::Unpack => {
-                let pack = collector.inspect(*self.variable_stack.last().unwrap());
-                let pack: &List = pack.as_ref().downcast_ref().unwrap();
+                let Some(pack) = self.variable_stack.last() else {
+                    return Err(self.fault(ErrorKind::StackUnderflow));
+                };
+                let pack = collector.inspect(*pack);
+                let Some(pack) = pack.as_ref().downcast_ref::<List>() else {
+                    return Err(self.fault(ErrorKind::NotList));
+                };
                 self.variable_stack.pop();
                 self.variable_stack.extend(&pack.0);
             }
+            ByteCode::Try(offset) => {
+                let offset = *offset as isize;
+                let frame = self.call_stack.last_mut().unwrap();
+                let Some(target) = frame.pointer.1.checked_add_signed(offset) else {
+                    return Err(self.fault(ErrorKind::OutOfProgram));
+                };
+                frame.handler_list.push(Handler {
+                    offset: target,
+                    stack_size,
+                    stack_len: self.variable_stack.len(),
+                });
+            }
+            ByteCode::EndTry => {
+                let frame = self.call_stack.last_mut().unwrap();
+                if frame.handler_list.pop().is_none() {
+                    return Err(self.fault(ErrorKind::NoHandler));
+                }
+            }
+            ByteCode::Throw => {
+                if n_floating == 0 {
+                    return Err(self.fault(ErrorKind::StackUnderflow));
+                }
+                let exception = self.variable_stack.pop().unwrap();
+                self.throw(collector, exception)?;
+            }
+            ByteCode::Yield(n_yielded) => {
+                let n_yielded = *n_yielded as usize;
+                if n_floating < n_yielded {
+                    return Err(self.fault(ErrorKind::StackUnderflow));
+                }
+                let Some(generator) = self.call_stack.last().unwrap().generator else {
+                    return Err(self.fault(ErrorKind::YieldOutsideGenerator));
+                };
+                let variable_list = self
+                    .variable_stack
+                    .drain(base..self.variable_stack.len() - n_yielded)
+                    .collect();
+                let mut frame = self.call_stack.pop().unwrap();
+                *self
+                    .generator_version_table
+                    .entry((frame.pointer.0, frame.version))
+                    .or_default() += 1;
+                frame.base -= base;
+                frame.stack_size -= base;
+                for handler in &mut frame.handler_list {
+                    handler.stack_size -= base;
+                    handler.stack_len -= base;
+                }
+                let state = GeneratorState::Suspended {
+                    frame,
+                    variable_list,
+                };
+                set_generator_state(collector, generator, state);
+                self.push_boolean(collector, true);
+            }
+            ByteCode::Resume(n_argument) => {
+                let n_argument = *n_argument as usize;
+                if n_floating < n_argument + 1 {
+                    return Err(self.fault(ErrorKind::StackUnderflow));
+                }
+                let generator = *self.variable_stack.last().unwrap();
+                // not kept shared, or the state cannot be replaced below
+                let object = collector
+                    .inspect(generator)
+                    .as_ref()
+                    .downcast_ref::<Generator>()
+                    .cloned();
+                let Some(object) = object else {
+                    return Err(self.fault(ErrorKind::NotGenerator));
+                };
+                let mut frame = match &object.state {
+                    GeneratorState::Running => return Err(self.fault(ErrorKind::GeneratorRunning)),
+                    GeneratorState::Finished => {
+                        let stack_len = self.variable_stack.len() - n_argument - 1;
+                        self.variable_stack.truncate(stack_len);
+                        self.push_boolean(collector, false);
+                        return Ok(());
+                    }
+                    GeneratorState::Created => {
+                        let (pointer, version, signature) = self.resolve(object.dispatch, true)?;
+                        self.check_arguments(signature, n_argument)?;
+                        Frame {
+                            pointer,
+                            version,
+                            base: 0,
+                            stack_size: 0,
+                            handler_list: Vec::new(),
+                            generator: Some(generator),
+                            n_result: signature.map(|signature| signature.n_result),
+                        }
+                    }
+                    GeneratorState::Suspended { frame, .. } => {
+                        // a generator yielded by another interpreter may enter a version
+                        // that is not loaded here
+                        let (module_id, _) = &frame.pointer;
+                        let loaded = self.module_table.get(module_id);
+                        if !loaded.is_some_and(|loaded| loaded.contains_key(&frame.version)) {
+                            let kind = ErrorKind::VersionUnloaded(*module_id, frame.version);
+                            return Err(self.fault(kind));
+                        }
+                        frame.clone()
+                    }
+                };
+                let n_saved = match &object.state {
+                    GeneratorState::Suspended { variable_list, .. } => variable_list.len(),
+                    _ => 0,
+                };
+                if self.call_stack.len() >= self.limits.max_call_depth
+                    || self.variable_stack.len() - 1 + n_saved > self.limits.max_stack_size
+                {
+                    return self.overflow(collector);
+                }
+                self.variable_stack.pop();
+                let base = self.variable_stack.len() - n_argument;
+                if let GeneratorState::Suspended { variable_list, .. } = &object.state {
+                    self.variable_stack
+                        .splice(base..base, variable_list.iter().copied());
+                }
+                frame.base += base;
+                frame.stack_size += base;
+                for handler in &mut frame.handler_list {
+                    handler.stack_size += base;
+                    handler.stack_len += base;
+                }
+                self.call_stack.last_mut().unwrap().stack_size = base;
+                if let GeneratorState::Suspended { .. } = &object.state {
+                    let pointer = (frame.pointer.0, frame.version);
+                    if let Some(count) = self.generator_version_table.get_mut(&pointer) {
+                        *count -= 1;
+                        if *count == 0 {
+                            self.generator_version_table.remove(&pointer);
+                        }
+                    }
+                }
+                self.call_stack.push(frame);
+                set_generator_state(collector, generator, GeneratorState::Running);
+            }
+        }
+        Ok(())
+    }
+
+    // results replace the arguments, false if native throws and the stacks are unwound
+    fn call_host(
+        &mut self,
+        collector: &mut dyn CollectorInterface,
+        dispatch: Dispatch,
+        native: &Native,
+        argument_offset: usize,
+    ) -> Result<bool, InterpreterError> {
+        let native_id = || format!("{}:{}", dispatch.module_id, dispatch.symbol);
+        if let Some(profiler) = &mut self.profiler {
+            profiler.record_native(&native_id());
+        }
+        let n_argument = self.variable_stack.len() - argument_offset;
+        let outcome = run_native(
+            native,
+            collector,
+            &mut self.variable_stack,
+            &mut self.waker,
+            argument_offset,
+        );
+        self.suspended = outcome.suspended;
+        if let Some(exception) = outcome.thrown {
+            self.throw(collector, exception)?;
+            return Ok(false);
+        }
+        if outcome.n_result != native.n_result as usize {
+            return Err(self.fault(ErrorKind::ResultMismatch {
+                native_id: native_id(),
+                expected: native.n_result as usize,
+                actual: outcome.n_result,
+            }));
+        }
+        self.variable_stack
+            .drain(argument_offset..argument_offset + n_argument);
+        Ok(true)
+    }
+
+    fn return_frame(&mut self, collector: &mut dyn CollectorInterface, n_returned: usize) {
+        let frame = self.call_stack.pop().unwrap();
+        self.variable_stack
+            .drain(frame.base..self.variable_stack.len() - n_returned);
+        if let Some(generator) = frame.generator {
+            set_generator_state(collector, generator, GeneratorState::Finished);
+            self.push_boolean(collector, false);
+        }
+    }
+
+    fn push_boolean(&mut self, collector: &mut dyn CollectorInterface, condition: bool) {
+        let condition = if condition {
+            collector.allocate(True.into())
+        } else {
+            collector.allocate(False.into())
+        };
+        self.variable_stack.push(condition);
+    }
+
+    // unwind frames without handler, then resume the innermost handler with the exception as
+    // its only floating variable beyond the ones it was installed with
+    fn throw(
+        &mut self,
+        collector: &mut dyn CollectorInterface,
+        exception: Address,
+    ) -> Result<(), InterpreterError> {
+        if self
+            .call_stack
+            .iter()
+            .all(|frame| frame.handler_list.is_empty())
+        {
+            return Err(self.fault(ErrorKind::Uncaught(exception)));
+        }
+        loop {
+            let frame = self.call_stack.last_mut().unwrap();
+            if let Some(handler) = frame.handler_list.pop() {
+                frame.pointer.1 = handler.offset;
+                frame.stack_size = handler.stack_size;
+                self.variable_stack.truncate(handler.stack_len);
+                self.variable_stack.push(exception);
+                return Ok(());
+            }
+            // generator unwound by the exception cannot be resumed anymore
+            if let Some(generator) = self.call_stack.pop().unwrap().generator {
+                set_generator_state(collector, generator, GeneratorState::Finished);
+            }
+        }
+    }
+
+    // thrown to guest as `StackOverflow` if there is a handler to catch it
+    fn overflow(&mut self, collector: &mut dyn CollectorInterface) -> Result<(), InterpreterError> {
+        if self
+            .call_stack
+            .iter()
+            .all(|frame| frame.handler_list.is_empty())
+        {
+            return Err(self.fault(ErrorKind::StackOverflow));
+        }
+        let backtrace = self.trace(1);
+        let exception = collector.allocate(StackOverflow { backtrace }.into());
+        self.throw(collector, exception)
+    }
+
+    fn test(
+        &self,
+        collector: &dyn CollectorInterface,
+        address: Address,
+    ) -> Result<bool, InterpreterError> {
+        let condition = collector.inspect(address);
+        if condition.as_ref().is::<True>() {
+            Ok(true)
+        } else if condition.as_ref().is::<False>() {
+            Ok(false)
+        } else {
+            Err(self.fault(ErrorKind::NotBoolean))
         }
     }
 
-    #[cfg(test)]
-    pub fn stack_view(&mut self, collector: &dyn CollectorInterface) -> Vec<Shared> {
+    fn jump(&mut self, offset: isize) -> Result<(), InterpreterError> {
+        let pointer = &mut self.call_stack.last_mut().unwrap().pointer;
+        let Some(target) = pointer.1.checked_add_signed(offset) else {
+            return Err(self.fault(ErrorKind::OutOfProgram));
+        };
+        pointer.1 = target;
+        Ok(())
+    }
+
+    pub fn stack_view(&self, collector: &dyn CollectorInterface) -> Vec<Shared> {
         self.variable_stack
             .iter()
             .map(|address| collector.inspect(*address))
@@ -201,18 +1575,68 @@ impl Interpreter {
     }
 }
 
+struct NativeOutcome {
+    n_result: usize,
+    thrown: Option<Address>,
+    suspended: bool,
+}
+
+// results are pushed after the arguments starting at offset
+fn run_native(
+    native: &Native,
+    collector: &mut dyn CollectorInterface,
+    variable_stack: &mut Vec<Address>,
+    waker: &mut Option<Waker>,
+    argument_offset: usize,
+) -> NativeOutcome {
+    let stack_len = variable_stack.len();
+    let mut view = OperateView {
+        collector,
+        variable_stack,
+        argument_offset,
+        thrown: None,
+        waker,
+        suspended: false,
+    };
+    native.operate(&mut view);
+    NativeOutcome {
+        n_result: view.variable_stack.len() - stack_len,
+        thrown: view.thrown,
+        suspended: view.suspended,
+    }
+}
+
+fn set_generator_state(
+    collector: &mut dyn CollectorInterface,
+    address: Address,
+    state: GeneratorState,
+) {
+    let mut generator = collector
+        .inspect(address)
+        .as_ref()
+        .downcast_ref::<Generator>()
+        .unwrap()
+        .clone();
+    generator.state = state;
+    collector.replace(address, generator.into());
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::objects::LeafObject;
+    use crate::assembler::assemble;
+    use crate::collector::TestCollector;
+    use crate::objects::StackOverflow;
+    use crate::verifier::VerifyErrorKind;
     use crate::GeneralInterface;
+    use std::sync::atomic::{AtomicBool, Ordering};
     use std::sync::Arc;
 
     fn main_module() -> ModuleId {
-        String::from("main")
+        Name::new("main")
     }
-    fn start_symbol() -> String {
-        String::from("start")
+    fn start_symbol() -> Name {
+        Name::new("start")
     }
     fn start_dispatch() -> Dispatch {
         Dispatch {
@@ -221,163 +1645,143 @@ mod tests {
         }
     }
 
-    #[derive(Default)]
-    struct Collector {
-        allocate_number: u32,
-        storage: HashMap<Address, Arc<dyn GeneralInterface>>,
-    }
-    impl CollectorInterface for Collector {
-        fn allocate(&mut self, owned: Owned) -> Address {
-            self.allocate_number += 1;
-            let address = (0, self.allocate_number);
-            self.storage.insert(address, owned.into());
-            address
-        }
-        fn inspect(&self, address: Address) -> Shared {
-            self.storage.get(&address).unwrap().clone().into()
-        }
-        fn replace(&mut self, address: Address, owned: Owned) -> Owned {
-            self.storage.insert(address, owned.into()).unwrap().into()
-        }
-    }
-
     #[test]
     fn simple_step() {
         let mut interp = Interpreter::new();
-        interp.load_module(Module {
-            id: main_module(),
-            program: vec![ByteCode::Return(0)],
-            symbol_table: [(start_symbol(), 0)].into_iter().collect(),
+        interp
+            .load_module(Module {
+                id: main_module(),
+                program: vec![ByteCode::Return(0)],
+                symbol_table: [(start_symbol(), 0)].into_iter().collect(),
+                ..Default::default()
+            })
+            .unwrap();
+        interp.push_call(start_dispatch(), 0).unwrap();
+        assert!(interp.has_step());
+        let mut collector = TestCollector::default();
+        interp.step(&mut collector).unwrap();
+        assert!(!interp.has_step());
+    }
+
+    fn push_literal<T: GeneralInterface + Clone>(
+        registry: &mut NativeRegistry,
+        literal: T,
+    ) -> ByteCode {
+        let native_id = format!("push {literal:?}");
+        registry.register(native_id.clone(), 1, move |context| {
+            let literal = context.allocate(literal.clone().into());
+            context.push_result(literal);
+        });
+        ByteCode::Operate(0, native_id)
+    }
+
+    fn assert_top<T: GeneralInterface + Eq>(registry: &mut NativeRegistry, expect: T) -> ByteCode {
+        let native_id = format!("assert {expect:?}");
+        registry.register(native_id.clone(), 0, move |context| {
+            let top = context.inspect(context.get_argument(0));
+            assert_eq!(top.as_ref().downcast_ref(), Some(&expect));
         });
-        interp.push_call(start_dispatch(), 0);
-        assert!(interp.has_step());
-        let mut collector = Collector::default();
-        interp.step(&mut collector);
-        assert!(!interp.has_step());
+        ByteCode::Operate(1, native_id)
     }
 
-    fn push_literal<T: GeneralInterface + Clone>(literal: T) -> ByteCode {
-        ByteCode::Operate(
-            0,
-            Box::new(move |context| {
-                let literal = context.allocate(literal.clone().into());
-                context.push_result(literal);
-            }),
-        )
+    // the integer natives with no standard counterpart
+    fn assert_eq(context: &mut dyn OperateContext) {
+        let operand = |index| -> Integer {
+            let int = context.inspect(context.get_argument(index));
+            *int.as_ref().downcast_ref().unwrap()
+        };
+        assert_eq!(operand(0), operand(1));
     }
 
-    fn assert_top<T: GeneralInterface + Eq>(expect: T) -> ByteCode {
-        ByteCode::Operate(
-            1,
-            Box::new(move |context| {
-                let top = context.inspect(context.get_argument(0));
-                assert_eq!(top.as_ref().downcast_ref(), Some(&expect));
-            }),
-        )
+    fn add_in_place(context: &mut dyn OperateContext) {
+        let operand = |index| -> Integer {
+            let int = context.inspect(context.get_argument(index));
+            *int.as_ref().downcast_ref().unwrap()
+        };
+        let int_c = Integer(operand(0).0 + operand(1).0);
+        let int_b = context.get_argument(1);
+        context.replace(int_b, int_c.into());
     }
 
-    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
-    struct I32(i32);
-    impl LeafObject for I32 {}
-    impl I32 {
-        fn operate_add_two(context: &mut dyn OperateContext) {
-            let int_a = context.inspect(context.get_argument(0));
-            let int_a: I32 = *int_a.as_ref().downcast_ref().unwrap();
-            let int_b = context.inspect(context.get_argument(1));
-            let int_b: I32 = *int_b.as_ref().downcast_ref().unwrap();
-            let int_c = context.allocate(I32(int_a.0 + int_b.0).into());
-            context.push_result(int_c);
-        }
-        fn operate_add_in_place(context: &mut dyn OperateContext) {
-            let int_a = context.inspect(context.get_argument(0));
-            let int_a: I32 = *int_a.as_ref().downcast_ref().unwrap();
-            let int_b = context.inspect(context.get_argument(1));
-            let int_b: I32 = *int_b.as_ref().downcast_ref().unwrap();
-            let int_c = I32(int_a.0 + int_b.0);
-            let int_b = context.get_argument(1);
-            context.replace(int_b, int_c.into());
-        }
-        fn operate_eq_two(context: &mut dyn OperateContext) {
-            let int_a = context.inspect(context.get_argument(0));
-            let int_a: I32 = *int_a.as_ref().downcast_ref().unwrap();
-            let int_b = context.inspect(context.get_argument(1));
-            let int_b: I32 = *int_b.as_ref().downcast_ref().unwrap();
-            let result: Owned = if int_a == int_b {
-                True.into()
-            } else {
-                False.into()
-            };
-            let result = context.allocate(result);
-            context.push_result(result);
-        }
+    fn registry() -> NativeRegistry {
+        let mut registry = NativeRegistry::standard();
+        registry.register("integer.add_in_place", 0, add_in_place);
+        registry.register("integer.assert_eq", 0, assert_eq);
+        registry
     }
 
     #[test]
-    fn add_two_i32() {
-        let mut interp = Interpreter::new();
-        interp.load_module(Module {
+    fn add_two_integers() {
+        let mut registry = registry();
+        let module = Module {
             id: main_module(),
             symbol_table: [(start_symbol(), 0)].into_iter().collect(),
             program: vec![
-                push_literal(I32(20)),
-                push_literal(I32(22)),
-                ByteCode::Operate(2, Box::new(I32::operate_add_two)),
-                assert_top(I32(42)),
+                push_literal(&mut registry, Integer(20)),
+                push_literal(&mut registry, Integer(22)),
+                ByteCode::Operate(2, "integer.add".into()),
+                assert_top(&mut registry, Integer(42)),
                 ByteCode::Return(0),
             ],
-        });
-        interp.push_call(start_dispatch(), 0);
-        let mut collector = Collector::default();
+            ..Default::default()
+        };
+        let mut interp = Interpreter::with_registry(registry);
+        interp.load_module(module).unwrap();
+        interp.push_call(start_dispatch(), 0).unwrap();
+        let mut collector = TestCollector::default();
         while interp.has_step() {
-            interp.step(&mut collector);
+            interp.step(&mut collector).unwrap();
         }
     }
 
     #[test]
-    fn add_i32_in_place() {
-        let mut interp = Interpreter::new();
-        interp.load_module(Module {
+    fn add_integers_in_place() {
+        let mut registry = registry();
+        let module = Module {
             id: main_module(),
             symbol_table: [(start_symbol(), 0)].into_iter().collect(),
             program: vec![
-                push_literal(I32(20)),
-                push_literal(I32(22)),
-                ByteCode::Operate(2, Box::new(I32::operate_add_in_place)),
-                assert_top(I32(42)),
+                push_literal(&mut registry, Integer(20)),
+                push_literal(&mut registry, Integer(22)),
+                ByteCode::Operate(2, "integer.add_in_place".into()),
+                assert_top(&mut registry, Integer(42)),
                 ByteCode::Copy(2),
-                assert_top(I32(20)),
+                assert_top(&mut registry, Integer(20)),
                 ByteCode::Return(0),
             ],
-        });
-        interp.push_call(start_dispatch(), 0);
-        let mut collector = Collector::default();
+            ..Default::default()
+        };
+        let mut interp = Interpreter::with_registry(registry);
+        interp.load_module(module).unwrap();
+        interp.push_call(start_dispatch(), 0).unwrap();
+        let mut collector = TestCollector::default();
         while interp.has_step() {
-            interp.step(&mut collector);
+            interp.step(&mut collector).unwrap();
         }
     }
 
     #[test]
     fn fib_10() {
         // in a very wasteful way...
-        let mut interp = Interpreter::new();
-        interp.load_module(Module {
+        let mut registry = registry();
+        let module = Module {
             id: main_module(),
             symbol_table: [(start_symbol(), 0)].into_iter().collect(),
             program: vec![
-                push_literal(I32(10)), // n
-                push_literal(I32(-1)), // _
-                push_literal(I32(0)),  // b
-                push_literal(I32(1)),  // a
-                push_literal(I32(1)),  // 1
-                push_literal(I32(1)),  // i
-                push_literal(I32(-1)), // _
+                push_literal(&mut registry, Integer(10)), // n
+                push_literal(&mut registry, Integer(-1)), // _
+                push_literal(&mut registry, Integer(0)),  // b
+                push_literal(&mut registry, Integer(1)),  // a
+                push_literal(&mut registry, Integer(1)),  // 1
+                push_literal(&mut registry, Integer(1)),  // i
+                push_literal(&mut registry, Integer(-1)), // _
                 // 'loop: T i' 1 a' a ? n => _ i 1 a b _ n
                 // i _ i 1 a b _ n
                 ByteCode::Copy(2),
                 // n i _ i 1 a b
                 ByteCode::Copy(8),
                 // ? n i _ i 1 a b
-                ByteCode::Operate(2, Box::new(I32::operate_eq_two)),
+                ByteCode::Operate(2, "integer.eq".into()),
                 // goto 'end
                 ByteCode::Jump(8),
                 // a ? n i _ i 1 a b
@@ -385,100 +1789,1138 @@ mod tests {
                 // b a ? n i _ i 1
                 ByteCode::Copy(9),
                 // a' a ? n i _ i 1
-                ByteCode::Operate(2, Box::new(I32::operate_add_in_place)),
+                ByteCode::Operate(2, "integer.add_in_place".into()),
                 // 1 a' a ? n i
                 ByteCode::Copy(8),
                 // i 1 a' a ? n
                 ByteCode::Copy(6),
                 // i' 1 a' a ? n
-                ByteCode::Operate(2, Box::new(I32::operate_add_in_place)),
+                ByteCode::Operate(2, "integer.add_in_place".into()),
                 // T i' 1 a' b ? n
-                push_literal(True),
+                push_literal(&mut registry, True),
                 // goto 'loop
                 ByteCode::Jump(-12),
                 // 'end: ? n i _ i 1 a
                 ByteCode::Copy(7),
-                assert_top(I32(55)),
+                assert_top(&mut registry, Integer(55)),
                 ByteCode::Return(0),
             ],
-        });
-        interp.push_call(start_dispatch(), 0);
-        let mut collector = Collector::default();
+            ..Default::default()
+        };
+        let mut interp = Interpreter::with_registry(registry);
+        interp.load_module(module).unwrap();
+        interp.push_call(start_dispatch(), 0).unwrap();
+        let mut collector = TestCollector::default();
+        while interp.has_step() {
+            interp.step(&mut collector).unwrap();
+        }
+    }
+
+    #[test]
+    fn fib_10_local() {
+        let mut registry = registry();
+        let module = Module {
+            id: main_module(),
+            symbol_table: [(start_symbol(), 0)].into_iter().collect(),
+            program: vec![
+                push_literal(&mut registry, Integer(10)), // 0: n
+                push_literal(&mut registry, Integer(1)),  // 1: i
+                push_literal(&mut registry, Integer(1)),  // 2: a
+                push_literal(&mut registry, Integer(0)),  // 3: b
+                push_literal(&mut registry, Integer(1)),  // 4: 1
+                // 'loop
+                ByteCode::LoadLocal(1),
+                ByteCode::LoadLocal(0),
+                ByteCode::Operate(2, "integer.eq".into()),
+                // goto 'end
+                ByteCode::JumpIf(17),
+                ByteCode::Pop,
+                ByteCode::Pop,
+                ByteCode::LoadLocal(2),
+                ByteCode::LoadLocal(3),
+                // a' b a
+                ByteCode::Operate(2, "integer.add".into()),
+                ByteCode::LoadLocal(2),
+                ByteCode::StoreLocal(3),
+                ByteCode::StoreLocal(2),
+                ByteCode::Pop,
+                ByteCode::Pop,
+                ByteCode::LoadLocal(1),
+                ByteCode::LoadLocal(4),
+                ByteCode::Operate(2, "integer.add".into()),
+                ByteCode::StoreLocal(1),
+                ByteCode::Pop,
+                ByteCode::Pop,
+                ByteCode::Goto(-21),
+                // 'end: n i
+                ByteCode::Pop,
+                ByteCode::Pop,
+                ByteCode::AssertFloating(5),
+                ByteCode::LoadLocal(0),
+                ByteCode::LoadLocal(3),
+                ByteCode::LoadLocal(2),
+                // a b n
+                ByteCode::Rotate(3),
+                // b n a
+                assert_top(&mut registry, Integer(34)),
+                ByteCode::Swap,
+                // n b a
+                assert_top(&mut registry, Integer(10)),
+                ByteCode::Pop,
+                ByteCode::Pop,
+                assert_top(&mut registry, Integer(55)),
+                ByteCode::Return(0),
+            ],
+            ..Default::default()
+        };
+        let mut interp = Interpreter::with_registry(registry);
+        interp.load_module(module).unwrap();
+        interp.push_call(start_dispatch(), 0).unwrap();
+        let mut collector = TestCollector::default();
         while interp.has_step() {
-            interp.step(&mut collector);
+            interp.step(&mut collector).unwrap();
         }
     }
 
     #[test]
     fn fib_10_recursive() {
         // in a very naive way...
-        let mut interp = Interpreter::new();
-        let fib_symbol = String::from("fib");
+        let mut registry = registry();
+        let fib_symbol = Name::new("fib");
         let fib_dispatch = Dispatch {
             module_id: main_module(),
-            symbol: fib_symbol.clone(),
+            symbol: fib_symbol,
         };
-        interp.load_module(Module {
+        let module = Module {
             id: main_module(),
-            symbol_table: [(start_symbol(), 0), (fib_symbol.clone(), 6)]
-                .into_iter()
-                .collect(),
+            symbol_table: [(start_symbol(), 0), (fib_symbol, 6)].into_iter().collect(),
             program: vec![
-                push_literal(I32(10)),
-                push_literal(fib_dispatch.clone()),
+                push_literal(&mut registry, Integer(10)),
+                push_literal(&mut registry, fib_dispatch),
                 ByteCode::Call(1),
                 ByteCode::AssertFloating(1),
-                assert_top(I32(55)),
+                assert_top(&mut registry, Integer(55)),
                 ByteCode::Return(0),
                 // fib
                 // n
                 ByteCode::AssertFloating(1),
                 // 1 n
-                push_literal(I32(1)),
+                push_literal(&mut registry, Integer(1)),
                 // ? 1 n
-                ByteCode::Operate(2, Box::new(I32::operate_eq_two)),
+                ByteCode::Operate(2, "integer.eq".into()),
                 // goto '1
                 ByteCode::Jump(19),
                 // n ? 1
                 ByteCode::Copy(3),
                 // 2 n
-                push_literal(I32(2)),
+                push_literal(&mut registry, Integer(2)),
                 // ? 2 n
-                ByteCode::Operate(2, Box::new(I32::operate_eq_two)),
+                ByteCode::Operate(2, "integer.eq".into()),
                 // goto '2
                 ByteCode::Jump(15),
                 // -1 ? 2 n
-                push_literal(I32(-1)),
+                push_literal(&mut registry, Integer(-1)),
                 // n -1
                 ByteCode::Copy(4),
                 // n' n
-                ByteCode::Operate(2, Box::new(I32::operate_add_two)),
-                push_literal(fib_dispatch.clone()),
+                ByteCode::Operate(2, "integer.add".into()),
+                push_literal(&mut registry, fib_dispatch),
                 ByteCode::Call(1),
                 ByteCode::AssertFloating(1),
                 // -2 fib(n') n
-                push_literal(I32(-2)),
+                push_literal(&mut registry, Integer(-2)),
                 // n -2 fib(n')
                 ByteCode::Copy(3),
                 // n'' n -2 fib(n')
-                ByteCode::Operate(2, Box::new(I32::operate_add_two)),
-                push_literal(fib_dispatch.clone()),
+                ByteCode::Operate(2, "integer.add".into()),
+                push_literal(&mut registry, fib_dispatch),
                 // fib(n'') n -2 fib(n')
                 ByteCode::Call(1),
                 ByteCode::AssertFloating(1),
                 // fib(n') fib(n'')
                 ByteCode::Copy(4),
-                ByteCode::Operate(2, Box::new(I32::operate_add_two)),
+                ByteCode::Operate(2, "integer.add".into()),
                 ByteCode::Return(1),
                 // '1 '2
-                push_literal(I32(1)),
+                push_literal(&mut registry, Integer(1)),
+                ByteCode::Return(1),
+            ],
+            ..Default::default()
+        };
+        let mut interp = Interpreter::with_registry(registry);
+        interp.load_module(module).unwrap();
+        interp.push_call(start_dispatch(), 0).unwrap();
+        let mut collector = TestCollector::default();
+        while interp.has_step() {
+            interp.step(&mut collector).unwrap();
+        }
+    }
+
+    #[test]
+    fn count_down_tail_call() {
+        let mut registry = registry();
+        let loop_dispatch = Dispatch {
+            module_id: main_module(),
+            symbol: Name::new("loop"),
+        };
+        let module = Module {
+            id: main_module(),
+            symbol_table: [(start_symbol(), 0), (Name::new("loop"), 6)]
+                .into_iter()
+                .collect(),
+            program: vec![
+                push_literal(&mut registry, Integer(1000)),
+                push_literal(&mut registry, loop_dispatch),
+                ByteCode::Call(1),
+                ByteCode::AssertFloating(1),
+                assert_top(&mut registry, Integer(0)),
+                ByteCode::Return(0),
+                // loop
+                // n
+                ByteCode::AssertFloating(1),
+                // 0 n
+                push_literal(&mut registry, Integer(0)),
+                // ? 0 n
+                ByteCode::Operate(2, "integer.eq".into()),
+                ByteCode::JumpIf(5),
+                // -1 0 n
+                push_literal(&mut registry, Integer(-1)),
+                // n -1 0 n
+                ByteCode::Copy(3),
+                // n' n -1 0 n
+                ByteCode::Operate(2, "integer.add".into()),
+                push_literal(&mut registry, loop_dispatch),
+                ByteCode::TailCall(1),
+                // 0 n
+                ByteCode::Copy(2),
                 ByteCode::Return(1),
             ],
+            ..Default::default()
+        };
+        let mut interp = Interpreter::with_registry(registry);
+        interp.load_module(module).unwrap();
+        interp.push_call(start_dispatch(), 0).unwrap();
+        let mut collector = TestCollector::default();
+        while interp.has_step() {
+            interp.step(&mut collector).unwrap();
+            assert!(interp.call_stack.len() <= 2);
+            assert!(interp.variable_stack.len() <= 6);
+        }
+    }
+
+    #[test]
+    fn preempt_infinite_loop() {
+        let module = Module {
+            id: main_module(),
+            symbol_table: [(start_symbol(), 0), (Name::new("end"), 1)]
+                .into_iter()
+                .collect(),
+            program: vec![ByteCode::Goto(-1), ByteCode::Return(0)],
+            ..Default::default()
+        };
+        let mut interp = Interpreter::with_registry(registry());
+        interp.load_module(module).unwrap();
+        interp.push_call(start_dispatch(), 0).unwrap();
+        let mut collector = TestCollector::default();
+        assert_eq!(interp.run(&mut collector, 100), Ok(RunStatus::OutOfFuel));
+        let continuation = interp.suspend();
+        assert!(!interp.has_step());
+
+        // interpreter is free for other executions in the meantime
+        let end_dispatch = Dispatch {
+            module_id: main_module(),
+            symbol: Name::new("end"),
+        };
+        interp.push_call(end_dispatch, 0).unwrap();
+        assert_eq!(interp.run(&mut collector, 100), Ok(RunStatus::Finished));
+        interp.reset();
+
+        interp.resume(continuation);
+        assert_eq!(interp.run(&mut collector, 100), Ok(RunStatus::OutOfFuel));
+        assert_eq!(interp.call_stack[0].pointer, (main_module(), 0));
+    }
+
+    #[test]
+    fn suspend_in_native() {
+        let mut registry = registry();
+        registry.register("integer.suspend", 1, |context| {
+            let result = context.allocate(Integer(3).into());
+            context.push_result(result);
+            context.suspend().unwrap()();
+        });
+        let module = Module {
+            id: main_module(),
+            symbol_table: [(start_symbol(), 0)].into_iter().collect(),
+            program: vec![
+                ByteCode::Operate(0, "integer.suspend".into()),
+                ByteCode::AssertFloating(1),
+                assert_top(&mut registry, Integer(3)),
+                ByteCode::Return(0),
+            ],
+            ..Default::default()
+        };
+        let mut interp = Interpreter::with_registry(registry);
+        interp.load_module(module).unwrap();
+        interp.push_call(start_dispatch(), 0).unwrap();
+        let woken = Arc::new(AtomicBool::new(false));
+        let waker_woken = woken.clone();
+        interp.set_waker(Some(Box::new(move || {
+            waker_woken.store(true, Ordering::SeqCst)
+        })));
+        let mut collector = TestCollector::default();
+        assert_eq!(interp.run(&mut collector, 100), Ok(RunStatus::Suspended));
+        assert!(woken.load(Ordering::SeqCst));
+        assert_eq!(interp.call_stack[0].pointer, (main_module(), 1));
+        let continuation = interp.suspend();
+
+        interp.resume(continuation);
+        assert_eq!(interp.run(&mut collector, 100), Ok(RunStatus::Finished));
+    }
+
+    fn run_fault(mut registry: NativeRegistry, program: Vec<ByteCode>) -> InterpreterError {
+        registry.register("integer.one", 1, |context| {
+            let one = context.allocate(Integer(1).into());
+            context.push_result(one);
+        });
+        registry.register("broken", 1, |_| {});
+        let mut interp = Interpreter::with_registry(registry);
+        interp
+            .load_module(Module {
+                id: main_module(),
+                symbol_table: [(start_symbol(), 0)].into_iter().collect(),
+                program,
+                ..Default::default()
+            })
+            .unwrap();
+        interp.push_call(start_dispatch(), 0).unwrap();
+        let mut collector = TestCollector::default();
+        loop {
+            if let Err(error) = interp.step(&mut collector) {
+                interp.abort();
+                return error;
+            }
+        }
+    }
+
+    #[test]
+    fn guest_fault() {
+        let one = || ByteCode::Operate(0, "integer.one".into());
+        let fault = |program| run_fault(registry(), program);
+        let at = |offset, kind| InterpreterError {
+            pointer: Some((main_module(), offset)),
+            kind,
+            backtrace: vec![TraceEntry {
+                module_id: main_module(),
+                symbol: Some(start_symbol()),
+                offset,
+            }],
+        };
+        // underflows are rejected on loading before any runtime check
+        for program in [
+            vec![ByteCode::Copy(1), ByteCode::Return(0)],
+            vec![ByteCode::PackFloating(0), ByteCode::Return(2)],
+        ] {
+            let module = Module {
+                id: main_module(),
+                symbol_table: [(start_symbol(), 0)].into_iter().collect(),
+                program,
+                ..Default::default()
+            };
+            assert!(matches!(
+                Interpreter::new().load_module(module),
+                Err(LoadError::Verify(VerifyError {
+                    kind: VerifyErrorKind::StackUnderflow,
+                    ..
+                }))
+            ));
+        }
+        assert_eq!(
+            fault(vec![one(), ByteCode::Jump(0), ByteCode::Return(0)]),
+            at(1, ErrorKind::NotBoolean)
+        );
+        assert_eq!(
+            fault(vec![one(), ByteCode::Call(0), ByteCode::Return(0)]),
+            at(1, ErrorKind::NotDispatch)
+        );
+        assert_eq!(
+            fault(vec![one(), ByteCode::Unpack, ByteCode::Return(0)]),
+            at(1, ErrorKind::NotList)
+        );
+        assert_eq!(
+            fault(vec![
+                one(),
+                ByteCode::AssertFloating(2),
+                ByteCode::Return(0)
+            ]),
+            at(
+                1,
+                ErrorKind::FloatingMismatch {
+                    expected: 2,
+                    actual: 1
+                }
+            )
+        );
+        assert_eq!(
+            fault(vec![
+                ByteCode::Operate(0, "broken".into()),
+                ByteCode::Return(0)
+            ]),
+            at(
+                0,
+                ErrorKind::ResultMismatch {
+                    native_id: String::from("broken"),
+                    expected: 1,
+                    actual: 0
+                }
+            )
+        );
+
+        let mut registry = registry();
+        let missing = push_literal(
+            &mut registry,
+            Dispatch {
+                module_id: main_module(),
+                symbol: Name::new("missing"),
+            },
+        );
+        assert_eq!(
+            run_fault(
+                registry,
+                vec![missing, ByteCode::Call(0), ByteCode::Return(0)]
+            ),
+            at(
+                1,
+                ErrorKind::UnknownSymbol(main_module(), Name::new("missing"))
+            )
+        );
+    }
+
+    #[test]
+    fn nested_backtrace() {
+        let mut registry = registry();
+        let inner_dispatch = Dispatch {
+            module_id: main_module(),
+            symbol: Name::new("inner"),
+        };
+        let module = Module {
+            id: main_module(),
+            symbol_table: [(start_symbol(), 0), (Name::new("inner"), 3)]
+                .into_iter()
+                .collect(),
+            program: vec![
+                push_literal(&mut registry, inner_dispatch),
+                ByteCode::Call(0),
+                ByteCode::Return(0),
+                // inner
+                push_literal(&mut registry, Integer(1)),
+                ByteCode::Call(0),
+                ByteCode::Return(0),
+            ],
+            ..Default::default()
+        };
+        let mut interp = Interpreter::with_registry(registry);
+        interp.load_module(module).unwrap();
+        interp.push_call(start_dispatch(), 0).unwrap();
+        let mut collector = TestCollector::default();
+        let error = loop {
+            if let Err(error) = interp.step(&mut collector) {
+                break error;
+            }
+        };
+        let entry = |symbol: &str, offset| TraceEntry {
+            module_id: main_module(),
+            symbol: Some(Name::new(symbol)),
+            offset,
+        };
+        assert_eq!(error.kind, ErrorKind::NotDispatch);
+        assert_eq!(error.backtrace, [entry("inner", 4), entry("start", 1)]);
+        assert_eq!(
+            error.to_string(),
+            "main:4: call on non-dispatch variable\n    at inner (main:4)\n    at start (main:1)"
+        );
+    }
+
+    #[test]
+    fn link_imports() {
+        let mut interp = Interpreter::new();
+        let lib = assemble(
+            "
+                .module lib
+                .export double
+                .symbol double 1 1
+                    return 1
+                .symbol hidden
+                    return 0
+                ",
+        )
+        .unwrap();
+        interp.load_module(lib).unwrap();
+        let main = assemble(
+            "
+                .module main
+                .import lib double
+                .import lib hidden
+                .import lib triple
+                .import other start
+                .import main start
+                .symbol start
+                    return 0
+                ",
+        )
+        .unwrap();
+        interp.load_module(main).unwrap();
+        let unresolved = |import: (&str, &str), kind| UnresolvedImport {
+            module_id: main_module(),
+            import: (Name::new(import.0), Name::new(import.1)),
+            kind,
+        };
+        assert_eq!(
+            interp.link(),
+            Err(LinkError {
+                unresolved_list: vec![
+                    unresolved(("lib", "hidden"), UnresolvedKind::NotExported),
+                    unresolved(("lib", "triple"), UnresolvedKind::UnknownSymbol),
+                    unresolved(("other", "start"), UnresolvedKind::UnknownModule),
+                ]
+            })
+        );
+
+        let unknown_export = assemble(".module other\n.export start\n.symbol end\nreturn 0");
+        assert!(matches!(
+            interp.load_module(unknown_export.unwrap()),
+            Err(LoadError::UnknownExport { symbol, .. }) if symbol == "start"
+        ));
+        let other = assemble(".module other\n.export start\n.symbol start\nreturn 0");
+        interp.load_module(other.unwrap()).unwrap();
+        assert_eq!(interp.link().unwrap_err().unresolved_list.len(), 2);
+    }
+
+    #[test]
+    fn call_not_exported() {
+        let mut registry = registry();
+        let lib_dispatch = |symbol: &str| Dispatch {
+            module_id: Name::new("lib"),
+            symbol: Name::new(symbol),
+        };
+        let mut program = Vec::new();
+        for symbol in ["double", "hidden"] {
+            program.extend([
+                push_literal(&mut registry, lib_dispatch(symbol)),
+                ByteCode::Call(0),
+                ByteCode::Return(0),
+            ]);
+        }
+        let main = Module {
+            id: main_module(),
+            symbol_table: [(start_symbol(), 0), (Name::new("call_hidden"), 3)]
+                .into_iter()
+                .collect(),
+            program,
+            ..Default::default()
+        };
+        let lib = assemble(
+            ".module lib
+.export double
+.symbol double
+return 0
+.symbol hidden
+return 0",
+        )
+        .unwrap();
+        let mut interp = Interpreter::with_registry(registry);
+        interp.load_module(main).unwrap();
+        interp.load_module(lib).unwrap();
+        interp.link().unwrap();
+        let mut collector = TestCollector::default();
+        interp.push_call(start_dispatch(), 0).unwrap();
+        while interp.has_step() {
+            interp.step(&mut collector).unwrap();
+        }
+
+        let call_hidden = Dispatch {
+            module_id: main_module(),
+            symbol: Name::new("call_hidden"),
+        };
+        interp.push_call(call_hidden, 0).unwrap();
+        let error = loop {
+            if let Err(error) = interp.step(&mut collector) {
+                break error;
+            }
+        };
+        assert_eq!(error.pointer, Some((main_module(), 4)));
+        assert_eq!(
+            error.kind,
+            ErrorKind::NotExported(Name::new("lib"), Name::new("hidden"))
+        );
+        interp.abort();
+
+        // host may enter any symbol
+        interp.push_call(lib_dispatch("hidden"), 0).unwrap();
+        interp.step(&mut collector).unwrap();
+        assert!(!interp.has_step());
+    }
+
+    #[test]
+    fn replace_running_module() {
+        let mut interp = Interpreter::with_registry(registry());
+        let load_version = |interp: &mut Interpreter, n| {
+            let inner_dispatch = Dispatch {
+                module_id: main_module(),
+                symbol: Name::new("inner"),
+            };
+            let registry = interp.registry_mut();
+            let module = Module {
+                id: main_module(),
+                symbol_table: [(start_symbol(), 0), (Name::new("inner"), 5)]
+                    .into_iter()
+                    .collect(),
+                program: vec![
+                    push_literal(registry, inner_dispatch),
+                    ByteCode::Call(0),
+                    ByteCode::AssertFloating(1),
+                    assert_top(registry, Integer(n)),
+                    ByteCode::Return(0),
+                    // inner
+                    push_literal(registry, Integer(n)),
+                    ByteCode::Return(1),
+                ],
+                ..Default::default()
+            };
+            interp.load_module(module).unwrap();
+        };
+        load_version(&mut interp, 0);
+        interp.push_call(start_dispatch(), 0).unwrap();
+        let mut collector = TestCollector::default();
+        interp.step(&mut collector).unwrap();
+        interp.step(&mut collector).unwrap();
+        assert_eq!(interp.call_stack().len(), 2);
+
+        load_version(&mut interp, 1);
+        assert_eq!(interp.current_version(main_module()), Some(1));
+        let in_use = interp.versions_in_use();
+        assert_eq!(in_use, [(main_module(), 0)].into_iter().collect());
+        assert!(interp.unload_stale(&Default::default()).is_empty());
+        // running frames finish with version 0
+        while interp.has_step() {
+            interp.step(&mut collector).unwrap();
+        }
+        assert!(interp.versions_in_use().is_empty());
+
+        interp.push_call(start_dispatch(), 0).unwrap();
+        assert_eq!(interp.call_stack()[0].version, 1);
+        assert_eq!(interp.unload_stale(&in_use), []);
+        assert_eq!(
+            interp.unload_stale(&Default::default()),
+            [(main_module(), 0)]
+        );
+        while interp.has_step() {
+            interp.step(&mut collector).unwrap();
+        }
+    }
+
+    #[test]
+    fn catch_exception() {
+        let mut registry = registry();
+        registry.register("integer.throw", 1, |context| {
+            let exception = context.allocate(Integer(7).into());
+            context.push_result(exception);
+            context.throw(exception);
+        });
+        let module = assemble(
+            "
+                .module main
+                .constant dispatch main throw
+                .constant integer 42
+                .constant integer 7
+                .constant integer 1
+                .constant integer 2
+                .symbol start
+                    try handler
+                    load_constant 0
+                    call 0
+                    end_try
+                    return 0
+                handler:
+                    assert_floating 1
+                    load_constant 1
+                    operate 2 integer.assert_eq
+                    return 0
+                .symbol throw
+                    load_constant 1
+                    throw
+                .symbol native
+                    load_constant 3
+                    try caught
+                    load_constant 4
+                    operate 0 integer.throw
+                    return 0
+                caught:             ; exception 1
+                    assert_floating 2
+                    load_constant 2
+                    operate 2 integer.assert_eq
+                    copy 3
+                    load_constant 3
+                    operate 2 integer.assert_eq
+                    end_try
+                    return 0
+            ",
+        )
+        .unwrap();
+        let offset_of = |instruction| {
+            module
+                .program
+                .iter()
+                .rposition(|entry| *entry == instruction)
+                .unwrap()
+        };
+        let end_try = offset_of(ByteCode::EndTry);
+        let throw = offset_of(ByteCode::Throw);
+        let symbol_dispatch = |symbol: &str| Dispatch {
+            module_id: main_module(),
+            symbol: Name::new(symbol),
+        };
+        let mut interp = Interpreter::with_registry(registry);
+        interp.load_module(module).unwrap();
+        let mut collector = TestCollector::default();
+        interp.push_call(start_dispatch(), 0).unwrap();
+        while interp.has_step() {
+            interp.step(&mut collector).unwrap();
+        }
+
+        interp.push_call(symbol_dispatch("native"), 0).unwrap();
+        let error = loop {
+            if let Err(error) = interp.step(&mut collector) {
+                break error;
+            }
+        };
+        // handler is removed once it catches
+        assert_eq!(error.kind, ErrorKind::NoHandler);
+        assert_eq!(error.pointer, Some((main_module(), end_try)));
+        interp.abort();
+
+        interp.push_call(symbol_dispatch("throw"), 0).unwrap();
+        let error = loop {
+            if let Err(error) = interp.step(&mut collector) {
+                break error;
+            }
+        };
+        assert!(matches!(error.kind, ErrorKind::Uncaught(_)));
+        assert_eq!(error.pointer, Some((main_module(), throw)));
+    }
+
+    #[test]
+    fn shared_constant() {
+        let source = "
+            .module main
+            .constant dispatch main inner
+            .symbol start
+                load_constant 0
+                call 0
+                load_constant 0
+                return 2
+            .symbol inner 0 1
+                load_constant 0
+                return 1
+        ";
+        let mut interp = Interpreter::new();
+        interp.load_module(assemble(source).unwrap()).unwrap();
+        let mut collector = TestCollector::default();
+        let run = |interp: &mut Interpreter, collector: &mut TestCollector| {
+            interp.push_call(start_dispatch(), 0).unwrap();
+            while interp.has_step() {
+                interp.step(collector).unwrap();
+            }
+            interp.reset()
+        };
+        let result_list = run(&mut interp, &mut collector);
+        assert_eq!(result_list.len(), 2);
+        assert_eq!(result_list[0], result_list[1]);
+        assert_eq!(run(&mut interp, &mut collector), result_list);
+        // allocated again for another collector stepping the same version
+        let mut other = TestCollector::default();
+        assert_eq!(run(&mut interp, &mut other).len(), 2);
+        assert_eq!(other.allocate_number, 1);
+        // and for the new version
+        interp.load_module(assemble(source).unwrap()).unwrap();
+        assert_ne!(run(&mut interp, &mut collector), result_list);
+        assert_eq!(collector.allocate_number, 2);
+    }
+
+    #[test]
+    fn replace_constant() {
+        let source = "
+            .module main
+            .constant integer 1
+            .symbol start
+                load_constant 0
+                load_constant 0
+                operate 2 integer.add_in_place
+                return 0
+        ";
+        let mut interp = Interpreter::with_registry(registry());
+        interp.load_module(assemble(source).unwrap()).unwrap();
+        interp.push_call(start_dispatch(), 0).unwrap();
+        let mut collector = TestCollector::default();
+        let error = loop {
+            if let Err(error) = interp.step(&mut collector) {
+                break error;
+            }
+        };
+        let ErrorKind::Uncaught(exception) = error.kind else {
+            panic!("unexpected error {error}")
+        };
+        let exception = collector.inspect(exception);
+        assert!(exception.as_ref().is::<ImmutableConstant>());
+        let constant = collector.inspect(interp.abort()[0]);
+        assert_eq!(constant.as_ref().downcast_ref(), Some(&Integer(1)));
+    }
+
+    #[test]
+    fn stack_overflow() {
+        let module = assemble(
+            "
+            .module main
+            .constant dispatch main recurse
+            .constant true
+            .symbol start
+                try handler
+                load_constant 0
+                call 0
+                end_try
+                return 0
+            handler:
+                assert_floating 1
+                return 1
+            .symbol recurse
+                load_constant 0
+                call 0
+                return 0
+            .symbol grow
+            loop:
+                load_constant 1
+                jump loop
+                return 0
+            ",
+        )
+        .unwrap();
+        let mut interp = Interpreter::new();
+        interp.load_module(module).unwrap();
+        interp.set_limits(Limits {
+            max_call_depth: 100,
+            max_stack_size: 50,
+        });
+        let mut collector = TestCollector::default();
+        interp.push_call(start_dispatch(), 0).unwrap();
+        while interp.has_step() {
+            interp.step(&mut collector).unwrap();
+        }
+        let result_list = interp.reset();
+        let exception = collector.inspect(result_list[0]);
+        let exception: &StackOverflow = exception.as_ref().downcast_ref().unwrap();
+        assert_eq!(exception.backtrace.len(), 100);
+        assert_eq!(exception.backtrace[0].symbol, Some(Name::new("recurse")));
+        assert_eq!(exception.backtrace[0].offset, 8);
+        assert_eq!(exception.backtrace[99].offset, 2);
+
+        let grow = Dispatch {
+            module_id: main_module(),
+            symbol: Name::new("grow"),
+        };
+        interp.push_call(grow, 0).unwrap();
+        let error = loop {
+            if let Err(error) = interp.step(&mut collector) {
+                break error;
+            }
+        };
+        assert_eq!(error.kind, ErrorKind::StackOverflow);
+        assert_eq!(error.pointer, Some((main_module(), 10)));
+        assert_eq!(error.backtrace.len(), 1);
+        interp.abort();
+
+        interp.set_limits(Limits {
+            max_call_depth: 0,
+            ..interp.limits()
+        });
+        let error = interp.push_call(start_dispatch(), 0).unwrap_err();
+        assert_eq!(error.kind, ErrorKind::StackOverflow);
+    }
+
+    #[test]
+    fn fault_before_step() {
+        let mut interp = Interpreter::new();
+        interp
+            .load_module(assemble(".module main\n.symbol start\nreturn 0").unwrap())
+            .unwrap();
+        interp.push_call(start_dispatch(), 0).unwrap();
+        let unknown = Dispatch {
+            module_id: Name::new("unknown"),
+            symbol: start_symbol(),
+        };
+        let error = interp.push_call(unknown, 0).unwrap_err();
+        assert_eq!(error.kind, ErrorKind::UnknownModule(Name::new("unknown")));
+        assert_eq!(error.pointer, Some((main_module(), 0)));
+        assert_eq!(error.backtrace[0].offset, 0);
+
+        interp.set_limits(Limits {
+            max_call_depth: 1,
+            ..interp.limits()
+        });
+        let error = interp.push_call(start_dispatch(), 0).unwrap_err();
+        assert_eq!(error.kind, ErrorKind::StackOverflow);
+        assert_eq!(error.backtrace.len(), 1);
+        assert_eq!(error.backtrace[0].offset, 0);
+    }
+
+    #[test]
+    fn push_call_above_stack() {
+        let mut interp = Interpreter::new();
+        interp
+            .load_module(assemble(".module main\n.symbol start\nreturn 0").unwrap())
+            .unwrap();
+        let error = interp.push_call(start_dispatch(), 1).unwrap_err();
+        assert_eq!(error.kind, ErrorKind::StackUnderflow);
+        assert!(!interp.has_step());
+    }
+
+    #[test]
+    fn local_above_host_variable() {
+        let source = ".module main\n.symbol start\nassert_floating 1\nload_local 0\nreturn 1";
+        let mut interp = Interpreter::new();
+        interp.load_module(assemble(source).unwrap()).unwrap();
+        let mut collector = TestCollector::default();
+        let host = collector.allocate(True.into());
+        let argument = collector.allocate(False.into());
+        interp.push_variable(host);
+        interp.push_variable(argument);
+        // the host variable is below the frame and not its local 0
+        interp.push_call(start_dispatch(), 1).unwrap();
+        while interp.has_step() {
+            interp.step(&mut collector).unwrap();
+        }
+        assert_eq!(interp.reset(), [host, argument]);
+    }
+
+    #[test]
+    fn tail_call_above_host_variable() {
+        let source = "
+            .module main
+            .constant dispatch main done
+            .symbol start
+                assert_floating 0
+                load_constant 0
+                tail_call 0
+            .symbol done
+                return 0
+        ";
+        let mut interp = Interpreter::new();
+        interp.load_module(assemble(source).unwrap()).unwrap();
+        let mut collector = TestCollector::default();
+        let host = collector.allocate(True.into());
+        interp.push_variable(host);
+        interp.push_call(start_dispatch(), 1).unwrap();
+        while interp.has_step() {
+            interp.step(&mut collector).unwrap();
+        }
+        assert_eq!(interp.reset(), [host]);
+    }
+
+    #[test]
+    fn check_arity() {
+        let lib = assemble(
+            "
+            .module lib
+            .export pair
+            .export one
+            .constant true
+            .symbol pair 0 2
+                load_constant 0
+                load_constant 0
+                return 2
+            .symbol one 1 1
+                return 1
+            ",
+        )
+        .unwrap();
+        let main = assemble(
+            "
+            .module main
+            .constant dispatch lib pair
+            .constant dispatch lib one
+            .symbol start
+                load_constant 1
+                call 0
+                return 0
+            .symbol tail 0 1
+                load_constant 0
+                tail_call 0
+            ",
+        )
+        .unwrap();
+        let mut interp = Interpreter::new();
+        interp.load_module(lib).unwrap();
+        interp.load_module(main).unwrap();
+        let mut collector = TestCollector::default();
+        let mut run = |interp: &mut Interpreter, symbol| {
+            let dispatch = Dispatch {
+                module_id: main_module(),
+                symbol: Name::new(symbol),
+            };
+            interp.push_call(dispatch, 0).unwrap();
+            let error = loop {
+                if let Err(error) = interp.step(&mut collector) {
+                    break error;
+                }
+            };
+            interp.abort();
+            error
+        };
+        let error = run(&mut interp, "start");
+        assert_eq!(
+            error.kind,
+            ErrorKind::ArgumentMismatch {
+                expected: 1,
+                actual: 0
+            }
+        );
+        assert_eq!(error.pointer, Some((main_module(), 1)));
+        let error = run(&mut interp, "tail");
+        assert_eq!(
+            error.kind,
+            ErrorKind::ReturnMismatch {
+                expected: 1,
+                actual: 2
+            }
+        );
+        assert_eq!(error.pointer, Some((main_module(), 4)));
+
+        let one = Dispatch {
+            module_id: Name::new("lib"),
+            symbol: Name::new("one"),
+        };
+        assert!(matches!(
+            interp.push_call(one, 0).unwrap_err().kind,
+            ErrorKind::ArgumentMismatch { .. }
+        ));
+        interp.push_variable(collector.allocate(True.into()));
+        interp.push_call(one, 0).unwrap();
+        while interp.has_step() {
+            interp.step(&mut collector).unwrap();
+        }
+        assert_eq!(interp.reset().len(), 1);
+    }
+
+    #[test]
+    fn unknown_entry() {
+        let mut interp = Interpreter::new();
+        let dispatch = Dispatch {
+            module_id: Name::new("missing"),
+            symbol: start_symbol(),
+        };
+        assert_eq!(
+            interp.push_call(dispatch, 0),
+            Err(InterpreterError {
+                pointer: None,
+                kind: ErrorKind::UnknownModule(Name::new("missing")),
+                backtrace: vec![]
+            })
+        );
+        assert!(!interp.has_step());
+    }
+
+    #[test]
+    fn call_host_module() {
+        let mut math = NativeRegistry::default();
+        math.register("add_two", 1, Integer::operate_add);
+        math.register("fail", 0, |context| {
+            let exception = context.allocate(Integer(7).into());
+            context.throw(exception);
         });
-        interp.push_call(start_dispatch(), 0);
-        let mut collector = Collector::default();
+        let math_dispatch = |symbol: &str| Dispatch {
+            module_id: Name::new("math"),
+            symbol: Name::new(symbol),
+        };
+        let module = assemble(
+            "
+                .module main
+                .import math add_two
+                .constant integer 20
+                .constant integer 22
+                .constant dispatch math add_two
+                .constant integer 42
+                .constant dispatch main sum
+                .constant integer 3
+                .constant dispatch math fail
+                .constant integer 7
+                .constant integer 1
+                .constant integer 2
+                .symbol start
+                    load_constant 0
+                    load_constant 1
+                    load_constant 2
+                    call 2
+                    assert_floating 1
+                    load_constant 3
+                    operate 2 integer.assert_eq
+                    load_constant 4
+                    call 0
+                    assert_floating 1
+                    load_constant 5
+                    operate 2 integer.assert_eq
+                    try handler
+                    load_constant 6
+                    call 0
+                    end_try
+                    return 0
+                handler:
+                    assert_floating 3
+                    load_constant 7
+                    operate 2 integer.assert_eq
+                    return 0
+                .symbol sum
+                    load_constant 8
+                    load_constant 9
+                    load_constant 2
+                    tail_call 2
+            ",
+        )
+        .unwrap();
+        let mut interp = Interpreter::with_registry(registry());
+        interp.load_module(module).unwrap();
+        assert!(interp.link().is_err());
+        interp.load_host_module(Name::new("math"), &math).unwrap();
+        interp.link().unwrap();
+        let mut collector = TestCollector::default();
+        interp.push_call(start_dispatch(), 0).unwrap();
         while interp.has_step() {
-            interp.step(&mut collector);
+            interp.step(&mut collector).unwrap();
         }
+        assert!(interp.reset().is_empty());
+
+        assert_eq!(
+            interp
+                .push_call(math_dispatch("add_two"), 2)
+                .unwrap_err()
+                .kind,
+            ErrorKind::NotGuest(Name::new("math"), Name::new("add_two"))
+        );
+        assert_eq!(
+            interp
+                .push_call(math_dispatch("missing"), 0)
+                .unwrap_err()
+                .kind,
+            ErrorKind::UnknownSymbol(Name::new("math"), Name::new("missing"))
+        );
+        assert!(matches!(
+            interp.load_host_module(main_module(), &math),
+            Err(LoadError::KindConflict(module_id)) if module_id == main_module()
+        ));
+        let math_module = Module {
+            id: Name::new("math"),
+            ..Default::default()
+        };
+        assert!(matches!(
+            interp.load_module(math_module),
+            Err(LoadError::KindConflict(module_id)) if module_id == "math"
+        ));
     }
 }
@@ -1,9 +1,20 @@
+pub mod assembler;
 pub mod closure;
 pub mod collector;
+pub mod debugger;
+pub mod disassembler;
+pub mod format;
+pub mod generator;
 pub mod interpreter;
+pub mod name;
+pub mod native;
+pub mod numeric;
 pub mod objects;
 pub mod portal;
+pub mod profiler;
 pub mod runner;
+pub mod tracer;
+pub mod verifier;
 
 use crate::collector::EnumerateReference;
 use std::any::Any;
@@ -0,0 +1,114 @@
+// interned identifiers of modules and symbols
+//
+// names are interned for the whole process and never freed, so comparing, hashing and
+// copying a name costs as much as an integer. the string is kept for debugging and
+// serialization, and names are ordered by it to keep sorted output stable
+//
+// every distinct name read from source or .gmkc files stays in memory until exit,
+// even after its module is unloaded. a process loading untrusted or an unbounded
+// number of modules grows with them, and panics after 2^32 distinct names
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fmt::{self, Debug, Display, Formatter};
+use std::ops::Deref;
+use std::sync::{LazyLock, RwLock};
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Name(u32);
+
+#[derive(Default)]
+struct Interner {
+    id_table: HashMap<&'static str, u32>,
+    name_list: Vec<&'static str>,
+}
+
+static INTERNER: LazyLock<RwLock<Interner>> = LazyLock::new(Default::default);
+
+impl Name {
+    pub fn new(name: &str) -> Self {
+        if let Some(interned) = Self::get(name) {
+            return interned;
+        }
+        let mut interner = INTERNER.write().unwrap();
+        if let Some(id) = interner.id_table.get(name) {
+            return Self(*id);
+        }
+        let name: &'static str = Box::leak(name.into());
+        let id = u32::try_from(interner.name_list.len()).expect("too many names");
+        interner.name_list.push(name);
+        interner.id_table.insert(name, id);
+        Self(id)
+    }
+
+    // without interning, none if no such name is ever created
+    pub fn get(name: &str) -> Option<Self> {
+        let interner = INTERNER.read().unwrap();
+        interner.id_table.get(name).map(|id| Self(*id))
+    }
+
+    pub fn as_str(self) -> &'static str {
+        INTERNER.read().unwrap().name_list[self.0 as usize]
+    }
+}
+
+impl Deref for Name {
+    type Target = str;
+    fn deref(&self) -> &Self::Target {
+        self.as_str()
+    }
+}
+
+impl From<&str> for Name {
+    fn from(name: &str) -> Self {
+        Self::new(name)
+    }
+}
+
+impl From<String> for Name {
+    fn from(name: String) -> Self {
+        Self::new(&name)
+    }
+}
+
+impl PartialEq<str> for Name {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl PartialEq<&str> for Name {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == *other
+    }
+}
+
+impl PartialOrd for Name {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Name {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let interner = INTERNER.read().unwrap();
+        interner.name_list[self.0 as usize].cmp(interner.name_list[other.0 as usize])
+    }
+}
+
+impl Debug for Name {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        Debug::fmt(self.as_str(), f)
+    }
+}
+
+impl Display for Name {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl Default for Name {
+    fn default() -> Self {
+        Self::new("")
+    }
+}
@@ -0,0 +1,90 @@
+use crate::interpreter::OperateContext;
+use crate::objects::{Closure, Float, Generator, Integer, Ready};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+pub type NativeId = String;
+
+type Function = Arc<dyn Fn(&mut dyn OperateContext) + Send + Sync>;
+
+#[derive(Clone)]
+pub struct Native {
+    pub n_result: u8, // number of results pushed on every invocation
+    function: Function,
+}
+
+impl Native {
+    pub fn operate(&self, context: &mut dyn OperateContext) {
+        (self.function)(context)
+    }
+}
+
+#[derive(Default, Clone)]
+pub struct NativeRegistry {
+    native_table: HashMap<NativeId, Native>,
+}
+
+impl NativeRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // natives that runner and closures depend on, and numeric operations
+    pub fn standard() -> Self {
+        let mut registry = Self::new();
+        registry.register("closure.apply", 2, Closure::operate_apply);
+        registry.register("closure.capture", 0, Closure::operate_capture);
+        registry.register("ready.new", 1, Ready::operate_new);
+        registry.register("generator.new", 1, Generator::operate_new);
+        registry.register("integer.add", 1, Integer::operate_add);
+        registry.register("integer.sub", 1, Integer::operate_sub);
+        registry.register("integer.mul", 1, Integer::operate_mul);
+        registry.register("integer.div", 1, Integer::operate_div);
+        registry.register("integer.rem", 1, Integer::operate_rem);
+        registry.register("integer.neg", 1, Integer::operate_neg);
+        registry.register("integer.eq", 1, Integer::operate_eq);
+        registry.register("integer.ne", 1, Integer::operate_ne);
+        registry.register("integer.lt", 1, Integer::operate_lt);
+        registry.register("integer.le", 1, Integer::operate_le);
+        registry.register("integer.gt", 1, Integer::operate_gt);
+        registry.register("integer.ge", 1, Integer::operate_ge);
+        registry.register("integer.to_float", 1, Integer::operate_to_float);
+        registry.register("integer.parse", 1, Integer::operate_parse);
+        registry.register("integer.format", 1, Integer::operate_format);
+        registry.register("float.add", 1, Float::operate_add);
+        registry.register("float.sub", 1, Float::operate_sub);
+        registry.register("float.mul", 1, Float::operate_mul);
+        registry.register("float.div", 1, Float::operate_div);
+        registry.register("float.rem", 1, Float::operate_rem);
+        registry.register("float.neg", 1, Float::operate_neg);
+        registry.register("float.eq", 1, Float::operate_eq);
+        registry.register("float.ne", 1, Float::operate_ne);
+        registry.register("float.lt", 1, Float::operate_lt);
+        registry.register("float.le", 1, Float::operate_le);
+        registry.register("float.gt", 1, Float::operate_gt);
+        registry.register("float.ge", 1, Float::operate_ge);
+        registry.register("float.to_integer", 1, Float::operate_to_integer);
+        registry.register("float.parse", 1, Float::operate_parse);
+        registry.register("float.format", 1, Float::operate_format);
+        registry
+    }
+
+    pub fn register<F>(&mut self, id: impl Into<NativeId>, n_result: u8, function: F)
+    where
+        F: Fn(&mut dyn OperateContext) + Send + Sync + 'static,
+    {
+        let native = Native {
+            n_result,
+            function: Arc::new(function),
+        };
+        self.native_table.insert(id.into(), native);
+    }
+
+    pub fn get(&self, id: &str) -> Option<&Native> {
+        self.native_table.get(id)
+    }
+
+    pub fn natives(&self) -> impl Iterator<Item = (&NativeId, &Native)> {
+        self.native_table.iter()
+    }
+}
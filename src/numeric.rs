@@ -0,0 +1,424 @@
+use crate::collector::Owned;
+use crate::interpreter::OperateContext;
+use crate::objects::{False, Float, Integer, NumericError, Text, True};
+
+// every native here pushes one result, or throws `NumericError` and pushes nothing
+// binary operations take the first pushed argument as the left operand
+
+type Outcome = Result<Owned, NumericError>;
+
+impl Integer {
+    // arguments: 2 Integer
+    // result: 1 Integer
+    pub fn operate_add(context: &mut dyn OperateContext) {
+        binary(context, |a: Self, b: Self| integer(a.0.checked_add(b.0)))
+    }
+
+    // arguments: 2 Integer
+    // result: 1 Integer
+    pub fn operate_sub(context: &mut dyn OperateContext) {
+        binary(context, |a: Self, b: Self| integer(a.0.checked_sub(b.0)))
+    }
+
+    // arguments: 2 Integer
+    // result: 1 Integer
+    pub fn operate_mul(context: &mut dyn OperateContext) {
+        binary(context, |a: Self, b: Self| integer(a.0.checked_mul(b.0)))
+    }
+
+    // arguments: 2 Integer
+    // result: 1 Integer, rounded toward zero
+    pub fn operate_div(context: &mut dyn OperateContext) {
+        binary(context, |a: Self, b: Self| {
+            nonzero(b)?;
+            integer(a.0.checked_div(b.0))
+        })
+    }
+
+    // arguments: 2 Integer
+    // result: 1 Integer with the sign of the dividend
+    pub fn operate_rem(context: &mut dyn OperateContext) {
+        binary(context, |a: Self, b: Self| {
+            nonzero(b)?;
+            integer(a.0.checked_rem(b.0))
+        })
+    }
+
+    // arguments: 1 Integer
+    // result: 1 Integer
+    pub fn operate_neg(context: &mut dyn OperateContext) {
+        unary(context, |a: Self| integer(a.0.checked_neg()))
+    }
+
+    // arguments: 2 Integer
+    // result: 1 True or False
+    pub fn operate_eq(context: &mut dyn OperateContext) {
+        binary(context, |a: Self, b: Self| boolean(a == b))
+    }
+
+    pub fn operate_ne(context: &mut dyn OperateContext) {
+        binary(context, |a: Self, b: Self| boolean(a != b))
+    }
+
+    pub fn operate_lt(context: &mut dyn OperateContext) {
+        binary(context, |a: Self, b: Self| boolean(a < b))
+    }
+
+    pub fn operate_le(context: &mut dyn OperateContext) {
+        binary(context, |a: Self, b: Self| boolean(a <= b))
+    }
+
+    pub fn operate_gt(context: &mut dyn OperateContext) {
+        binary(context, |a: Self, b: Self| boolean(a > b))
+    }
+
+    pub fn operate_ge(context: &mut dyn OperateContext) {
+        binary(context, |a: Self, b: Self| boolean(a >= b))
+    }
+
+    // arguments: 1 Integer
+    // result: 1 Float, nearest to the integer beyond 2^53
+    pub fn operate_to_float(context: &mut dyn OperateContext) {
+        unary(context, |a: Self| Ok(Float(a.0 as f64).into()))
+    }
+
+    // arguments: 1 Text of decimal digits with optional sign
+    // result: 1 Integer
+    pub fn operate_parse(context: &mut dyn OperateContext) {
+        let outcome = text(context).and_then(|text| match text.parse::<i64>() {
+            Ok(value) => integer(Some(value)),
+            Err(_) => Err(NumericError::InvalidText),
+        });
+        finish(context, outcome)
+    }
+
+    // arguments: 1 Integer
+    // result: 1 Text
+    pub fn operate_format(context: &mut dyn OperateContext) {
+        unary(context, |a: Self| Ok(Text(a.0.to_string()).into()))
+    }
+}
+
+impl Float {
+    // arguments: 2 Float
+    // result: 1 Float
+    pub fn operate_add(context: &mut dyn OperateContext) {
+        binary(context, |a: Self, b: Self| float(a.0 + b.0))
+    }
+
+    pub fn operate_sub(context: &mut dyn OperateContext) {
+        binary(context, |a: Self, b: Self| float(a.0 - b.0))
+    }
+
+    pub fn operate_mul(context: &mut dyn OperateContext) {
+        binary(context, |a: Self, b: Self| float(a.0 * b.0))
+    }
+
+    // infinity or NaN on zero divisor, never throws
+    pub fn operate_div(context: &mut dyn OperateContext) {
+        binary(context, |a: Self, b: Self| float(a.0 / b.0))
+    }
+
+    pub fn operate_rem(context: &mut dyn OperateContext) {
+        binary(context, |a: Self, b: Self| float(a.0 % b.0))
+    }
+
+    // arguments: 1 Float
+    // result: 1 Float
+    pub fn operate_neg(context: &mut dyn OperateContext) {
+        unary(context, |a: Self| float(-a.0))
+    }
+
+    // arguments: 2 Float
+    // result: 1 True or False, NaN is unequal and unordered to everything
+    pub fn operate_eq(context: &mut dyn OperateContext) {
+        binary(context, |a: Self, b: Self| boolean(a == b))
+    }
+
+    pub fn operate_ne(context: &mut dyn OperateContext) {
+        binary(context, |a: Self, b: Self| boolean(a != b))
+    }
+
+    pub fn operate_lt(context: &mut dyn OperateContext) {
+        binary(context, |a: Self, b: Self| boolean(a < b))
+    }
+
+    pub fn operate_le(context: &mut dyn OperateContext) {
+        binary(context, |a: Self, b: Self| boolean(a <= b))
+    }
+
+    pub fn operate_gt(context: &mut dyn OperateContext) {
+        binary(context, |a: Self, b: Self| boolean(a > b))
+    }
+
+    pub fn operate_ge(context: &mut dyn OperateContext) {
+        binary(context, |a: Self, b: Self| boolean(a >= b))
+    }
+
+    // arguments: 1 Float
+    // result: 1 Integer, rounded toward zero
+    pub fn operate_to_integer(context: &mut dyn OperateContext) {
+        unary(context, |a: Self| {
+            // -2^63 and 2^63 are exact, unlike `i64::MAX as f64`. NaN fails both
+            let bound = 2f64.powi(63);
+            let value = a.0.trunc();
+            if value >= -bound && value < bound {
+                integer(Some(value as i64))
+            } else {
+                Err(NumericError::Overflow)
+            }
+        })
+    }
+
+    // arguments: 1 Text, also accepting `inf` and `NaN`
+    // result: 1 Float
+    pub fn operate_parse(context: &mut dyn OperateContext) {
+        let outcome = text(context).and_then(|text| match text.parse() {
+            Ok(value) => float(value),
+            Err(_) => Err(NumericError::InvalidText),
+        });
+        finish(context, outcome)
+    }
+
+    // arguments: 1 Float
+    // result: 1 Text, the shortest one that parses back to the same float
+    pub fn operate_format(context: &mut dyn OperateContext) {
+        unary(context, |a: Self| Ok(Text(a.0.to_string()).into()))
+    }
+}
+
+fn operand<T: Copy + 'static>(context: &dyn OperateContext, index: u8) -> Result<T, NumericError> {
+    let object = context.inspect(context.get_argument(index));
+    let operand = object.as_ref().downcast_ref().copied();
+    operand.ok_or(NumericError::TypeMismatch)
+}
+
+fn text(context: &dyn OperateContext) -> Result<String, NumericError> {
+    let object = context.inspect(context.get_argument(0));
+    let text: Option<&Text> = object.as_ref().downcast_ref();
+    Ok(text.ok_or(NumericError::TypeMismatch)?.0.clone())
+}
+
+fn finish(context: &mut dyn OperateContext, outcome: Outcome) {
+    match outcome {
+        Ok(result) => {
+            let result = context.allocate(result);
+            context.push_result(result);
+        }
+        Err(error) => {
+            let error = context.allocate(error.into());
+            context.throw(error);
+        }
+    }
+}
+
+fn unary<T: Copy + 'static>(context: &mut dyn OperateContext, operate: impl FnOnce(T) -> Outcome) {
+    let outcome = operand(context, 0).and_then(operate);
+    finish(context, outcome)
+}
+
+fn binary<T: Copy + 'static>(
+    context: &mut dyn OperateContext,
+    operate: impl FnOnce(T, T) -> Outcome,
+) {
+    let outcome = operand(context, 0).and_then(|a| operate(a, operand(context, 1)?));
+    finish(context, outcome)
+}
+
+fn integer(value: Option<i64>) -> Outcome {
+    match value {
+        Some(value) => Ok(Integer(value).into()),
+        None => Err(NumericError::Overflow),
+    }
+}
+
+fn float(value: f64) -> Outcome {
+    Ok(Float(value).into())
+}
+
+fn nonzero(divisor: Integer) -> Result<(), NumericError> {
+    if divisor.0 == 0 {
+        Err(NumericError::DivideByZero)
+    } else {
+        Ok(())
+    }
+}
+
+fn boolean(condition: bool) -> Outcome {
+    Ok(if condition { True.into() } else { False.into() })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::assembler::assemble;
+    use crate::collector::{Shared, TestCollector};
+    use crate::interpreter::{Constant, Interpreter};
+    use crate::name::Name;
+    use crate::objects::{Dispatch, False, Float, Integer, NumericError, Text, True};
+    use crate::runner::CollectorInterface;
+    use crate::GeneralInterface;
+
+    // load the arguments, apply each native to the stack top in turn, and return the last
+    // result or the thrown exception
+    fn evaluate(argument_list: &[Constant], operation_list: &[(u8, &str)]) -> Shared {
+        let mut source = String::from(".module main\n");
+        for argument in argument_list {
+            source += &format!(".constant {argument}\n");
+        }
+        source += ".symbol start\ntry handler\n";
+        for index in 0..argument_list.len() {
+            source += &format!("load_constant {index}\n");
+        }
+        for (n_argument, native_id) in operation_list {
+            source += &format!("operate {n_argument} {native_id}\n");
+        }
+        source += "end_try\nreturn 1\nhandler:\nreturn 1\n";
+
+        let mut interp = Interpreter::new();
+        interp.load_module(assemble(&source).unwrap()).unwrap();
+        let dispatch = Dispatch {
+            module_id: Name::new("main"),
+            symbol: Name::new("start"),
+        };
+        interp.push_call(dispatch, 0).unwrap();
+        let mut collector = TestCollector::default();
+        while interp.has_step() {
+            interp.step(&mut collector).unwrap();
+        }
+        let result_list = interp.reset();
+        assert_eq!(result_list.len(), 1);
+        collector.inspect(result_list[0])
+    }
+
+    fn integer(native_id: &str, a: i64, b: i64) -> Shared {
+        let argument_list = [Constant::Integer(a), Constant::Integer(b)];
+        evaluate(&argument_list, &[(2, native_id)])
+    }
+
+    fn float(native_id: &str, a: f64, b: f64) -> Shared {
+        let argument_list = [Constant::Float(a), Constant::Float(b)];
+        evaluate(&argument_list, &[(2, native_id)])
+    }
+
+    fn convert(argument: Constant, native_id: &str) -> Shared {
+        evaluate(&[argument], &[(1, native_id)])
+    }
+
+    fn assert_object<T: GeneralInterface + PartialEq>(object: Shared, expect: T) {
+        assert_eq!(object.as_ref().downcast_ref(), Some(&expect));
+    }
+
+    #[test]
+    fn integer_arithmetic() {
+        assert_object(integer("integer.add", 7, -2), Integer(5));
+        assert_object(integer("integer.sub", 7, -2), Integer(9));
+        assert_object(integer("integer.mul", 7, -2), Integer(-14));
+        assert_object(integer("integer.div", -7, 2), Integer(-3));
+        assert_object(integer("integer.rem", -7, 2), Integer(-1));
+        assert_object(convert(Constant::Integer(7), "integer.neg"), Integer(-7));
+
+        let overflow = NumericError::Overflow;
+        assert_object(integer("integer.add", i64::MAX, 1), overflow);
+        assert_object(integer("integer.sub", i64::MIN, 1), overflow);
+        assert_object(integer("integer.mul", i64::MAX, 2), overflow);
+        assert_object(integer("integer.div", i64::MIN, -1), overflow);
+        assert_object(
+            convert(Constant::Integer(i64::MIN), "integer.neg"),
+            overflow,
+        );
+        assert_object(integer("integer.div", 1, 0), NumericError::DivideByZero);
+        assert_object(integer("integer.rem", 1, 0), NumericError::DivideByZero);
+    }
+
+    #[test]
+    fn float_arithmetic() {
+        assert_object(float("float.add", 0.5, 0.25), Float(0.75));
+        assert_object(float("float.sub", 0.5, 0.25), Float(0.25));
+        assert_object(float("float.mul", 0.5, -4.), Float(-2.));
+        assert_object(float("float.div", 1., 0.), Float(f64::INFINITY));
+        assert_object(float("float.rem", -7.5, 2.), Float(-1.5));
+        assert_object(convert(Constant::Float(0.5), "float.neg"), Float(-0.5));
+        let nan = float("float.div", 0., 0.);
+        assert!(nan.as_ref().downcast_ref::<Float>().unwrap().0.is_nan());
+    }
+
+    #[test]
+    fn comparison() {
+        for (native_id, expect) in [
+            ("eq", [false, true, false]),
+            ("ne", [true, false, true]),
+            ("lt", [true, false, false]),
+            ("le", [true, true, false]),
+            ("gt", [false, false, true]),
+            ("ge", [false, true, true]),
+        ] {
+            for ((a, b), expect) in [(1, 2), (2, 2), (2, 1)].into_iter().zip(expect) {
+                let integer = integer(&format!("integer.{native_id}"), a, b);
+                let float = float(&format!("float.{native_id}"), a as _, b as _);
+                for result in [integer, float] {
+                    assert_eq!(result.as_ref().is::<True>(), expect);
+                    assert_eq!(result.as_ref().is::<False>(), !expect);
+                }
+            }
+            let nan = float(&format!("float.{native_id}"), f64::NAN, f64::NAN);
+            assert_eq!(nan.as_ref().is::<True>(), native_id == "ne");
+        }
+    }
+
+    #[test]
+    fn conversion() {
+        let to_float = |value| convert(Constant::Integer(value), "integer.to_float");
+        assert_object(to_float(-3), Float(-3.));
+        assert_object(to_float(i64::MAX), Float(2f64.powi(63)));
+
+        let to_integer = |value| convert(Constant::Float(value), "float.to_integer");
+        assert_object(to_integer(2.9), Integer(2));
+        assert_object(to_integer(-2.9), Integer(-2));
+        assert_object(to_integer(-(2f64.powi(63))), Integer(i64::MIN));
+        assert_object(to_integer(2f64.powi(63)), NumericError::Overflow);
+        assert_object(to_integer(f64::NEG_INFINITY), NumericError::Overflow);
+        assert_object(to_integer(f64::NAN), NumericError::Overflow);
+
+        let mixed = evaluate(
+            &[Constant::Integer(1), Constant::Float(1.)],
+            &[(2, "integer.add")],
+        );
+        assert_object(mixed, NumericError::TypeMismatch);
+    }
+
+    #[test]
+    fn format_and_parse() {
+        let format = |argument, native_id| evaluate(&[argument], &[(1, native_id)]);
+        assert_object(
+            format(Constant::Integer(-42), "integer.format"),
+            Text("-42".into()),
+        );
+        assert_object(
+            format(Constant::Float(0.1), "float.format"),
+            Text("0.1".into()),
+        );
+
+        let round_trip = |argument, prefix| {
+            let format = format!("{prefix}.format");
+            let parse = format!("{prefix}.parse");
+            evaluate(&[argument], &[(1, &format), (1, &parse)])
+        };
+        assert_object(
+            round_trip(Constant::Integer(i64::MIN), "integer"),
+            Integer(i64::MIN),
+        );
+        assert_object(round_trip(Constant::Float(0.1), "float"), Float(0.1));
+        assert_object(round_trip(Constant::Float(1e300), "float"), Float(1e300));
+
+        let parse_float_text = |native_id| {
+            evaluate(
+                &[Constant::Float(0.5)],
+                &[(1, "float.format"), (1, native_id)],
+            )
+        };
+        assert_object(parse_float_text("integer.parse"), NumericError::InvalidText);
+        assert_object(parse_float_text("float.parse"), Float(0.5));
+        let parse_integer = convert(Constant::Integer(1), "integer.parse");
+        assert_object(parse_integer, NumericError::TypeMismatch);
+    }
+}
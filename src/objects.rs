@@ -1,5 +1,6 @@
 use crate::collector::{Address, EnumerateReference};
-use crate::interpreter::{ModuleId, OperateContext};
+use crate::interpreter::{Frame, ModuleId, OperateContext, TraceEntry};
+use crate::name::Name;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Intermediate;
@@ -32,10 +33,10 @@ impl EnumerateReference for List {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct Dispatch {
     pub module_id: ModuleId,
-    pub symbol: String,
+    pub symbol: Name,
     // debug print
 }
 impl LeafObject for Dispatch {}
@@ -53,6 +54,74 @@ impl EnumerateReference for Closure {
     }
 }
 
+#[derive(Debug, Clone)]
+pub struct Generator {
+    pub dispatch: Dispatch,
+    pub state: GeneratorState,
+}
+impl EnumerateReference for Generator {
+    fn enumerate_reference(&self, callback: &mut dyn FnMut(Address)) {
+        if let GeneratorState::Suspended { variable_list, .. } = &self.state {
+            for address in variable_list {
+                callback(*address);
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum GeneratorState {
+    Created,
+    // stack sizes of frame are relative to the start of variable list
+    Suspended {
+        frame: Frame,
+        variable_list: Vec<Address>,
+    },
+    Running,
+    Finished,
+}
+
+// 64-bit signed, arithmetic throws `NumericError::Overflow` instead of wrapping
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Integer(pub i64);
+impl LeafObject for Integer {}
+
+// IEEE 754 double precision
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+pub struct Float(pub f64);
+impl LeafObject for Float {}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Text(pub String);
+impl LeafObject for Text {}
+
+// thrown by numeric natives
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NumericError {
+    Overflow, // also for floats out of integer range or NaN on conversion
+    DivideByZero,
+    InvalidText,
+    TypeMismatch, // argument of another type than the native operates on
+}
+impl LeafObject for NumericError {}
+
+// thrown by other natives given an argument of another type than they take
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TypeMismatch;
+impl LeafObject for TypeMismatch {}
+
+// thrown when a native replaces a module constant, which is shared by every task
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ImmutableConstant;
+impl LeafObject for ImmutableConstant {}
+
+// thrown when interpreter limits are exceeded
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StackOverflow {
+    pub backtrace: Vec<TraceEntry>, // innermost frame first
+}
+impl LeafObject for StackOverflow {}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Pending;
 impl LeafObject for Pending {}
@@ -1,6 +1,6 @@
 use crate::collector::Address;
 use crate::TaskId;
-use std::collections::{HashMap, HashSet};
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::sync::atomic::{AtomicU32, Ordering};
 use std::sync::{Arc, Mutex};
 use std::thread::{park, Thread, ThreadId};
@@ -17,6 +17,11 @@ pub type Task = (TaskId, Address);
 struct Peer {
     poll_list: Mutex<Vec<Task>>,
     pending_set: Mutex<HashSet<Task>>,
+    preempted_list: Mutex<VecDeque<Task>>, // never stolen, execution state is kept by peer
+    suspended_set: Mutex<HashSet<Task>>,   // suspended mid-frame, woken into preempted_list
+    // tasks being polled with a frame waker, and whether woken before suspended_set is
+    // updated
+    woken_table: Mutex<HashMap<Task, bool>>,
     thread: Thread,
 }
 
@@ -25,6 +30,19 @@ impl Portal {
         Self::default()
     }
 
+    // peers are fixed before the portal is shared
+    pub fn add_peer(&mut self, thread: Thread) {
+        let peer = Peer {
+            poll_list: Default::default(),
+            pending_set: Default::default(),
+            preempted_list: Default::default(),
+            suspended_set: Default::default(),
+            woken_table: Default::default(),
+            thread: thread.clone(),
+        };
+        self.peer_table.insert(thread.id(), peer);
+    }
+
     pub fn activative_peer(&self) {
         for peer in self.peer_table.values() {
             peer.thread.unpark();
@@ -55,6 +73,54 @@ impl Portal {
             .insert(task);
     }
 
+    // requeue a task that ran out of fuel, after tasks already waiting on the peer
+    pub fn preempt(&self, id: ThreadId, task: Task) {
+        self.peer_table
+            .get(&id)
+            .unwrap()
+            .preempted_list
+            .lock()
+            .unwrap()
+            .push_back(task);
+    }
+
+    // park a task suspended by a native in the middle of a poll, execution state is kept
+    // by peer so it is requeued as preempted once woken
+    pub fn suspend_frame(&self, id: ThreadId, task: Task) {
+        let peer = self.peer_table.get(&id).unwrap();
+        let mut suspended_set = peer.suspended_set.lock().unwrap();
+        if peer.woken_table.lock().unwrap().remove(&task) == Some(true) {
+            peer.preempted_list.lock().unwrap().push_back(task);
+        } else {
+            suspended_set.insert(task);
+        }
+    }
+
+    // for the poll of a task, which ends with either `suspend_frame` or `retire_frame_waker`
+    pub fn frame_waker(self: &Arc<Self>, id: ThreadId, task: Task) -> Box<dyn FnOnce() + Send> {
+        let peer = self.peer_table.get(&id).unwrap();
+        peer.woken_table.lock().unwrap().insert(task, false);
+        let waker_self = self.clone();
+        Box::new(move || {
+            let peer = waker_self.peer_table.get(&id).unwrap();
+            let mut suspended_set = peer.suspended_set.lock().unwrap();
+            if suspended_set.remove(&task) {
+                peer.preempted_list.lock().unwrap().push_back(task);
+                drop(suspended_set);
+                waker_self.activative_peer();
+            } else if let Some(woken) = peer.woken_table.lock().unwrap().get_mut(&task) {
+                *woken = true;
+            }
+            // otherwise the poll ended without suspending, nothing to wake
+        })
+    }
+
+    // the poll ended without suspending the task
+    pub fn retire_frame_waker(&self, id: ThreadId, task: Task) {
+        let peer = self.peer_table.get(&id).unwrap();
+        peer.woken_table.lock().unwrap().remove(&task);
+    }
+
     pub fn waker(self: &Arc<Self>, id: ThreadId, task: Task) -> Box<dyn FnOnce()> {
         let waker_self = self.clone();
         Box::new(move || {
@@ -79,6 +145,17 @@ impl Portal {
             {
                 return task;
             }
+            if let Some(task) = self
+                .peer_table
+                .get(&id)
+                .unwrap()
+                .preempted_list
+                .lock()
+                .unwrap()
+                .pop_front()
+            {
+                return task;
+            }
             for (peer_id, peer) in self.peer_table.iter() {
                 if *peer_id == id {
                     continue;
@@ -91,3 +168,24 @@ impl Portal {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread::current;
+
+    #[test]
+    fn retired_frame_waker() {
+        let mut portal = Portal::new();
+        portal.add_peer(current());
+        let portal = Arc::new(portal);
+        let id = current().id();
+        let task = (0, (0, 1));
+        let waker = portal.frame_waker(id, task);
+        portal.retire_frame_waker(id, task);
+        waker();
+        let peer = portal.peer_table.get(&id).unwrap();
+        assert!(peer.woken_table.lock().unwrap().is_empty());
+        assert!(peer.preempted_list.lock().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,157 @@
+// opt-in guest profiling, enabled by `Interpreter::set_profiler`
+//
+// functions are named by module id and the nearest symbol at or before the
+// stepped instruction, as in backtraces. folded stacks list frames outermost
+// first, one line per distinct stack with its sample count, as consumed by
+// flamegraph tools
+use crate::interpreter::{ModuleId, TraceEntry};
+use crate::name::Name;
+use crate::native::NativeId;
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::num::NonZeroU64;
+use std::time::Duration;
+
+pub type Function = (ModuleId, Name);
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FunctionProfile {
+    pub n_instruction: u64,
+    pub time: Duration, // spent stepping instructions of the function itself
+}
+
+#[derive(Debug, Clone)]
+pub struct Profiler {
+    sample_interval: NonZeroU64, // instructions stepped between two call stack samples
+    n_step: u64,
+    function_table: HashMap<Function, FunctionProfile>,
+    native_table: HashMap<NativeId, u64>,
+    sample_table: HashMap<Vec<Function>, u64>,
+}
+
+impl Default for Profiler {
+    fn default() -> Self {
+        Self::new(NonZeroU64::new(100).unwrap())
+    }
+}
+
+impl Profiler {
+    pub fn new(sample_interval: NonZeroU64) -> Self {
+        Self {
+            sample_interval,
+            n_step: 0,
+            function_table: Default::default(),
+            native_table: Default::default(),
+            sample_table: Default::default(),
+        }
+    }
+
+    pub fn functions(&self) -> impl Iterator<Item = (&Function, &FunctionProfile)> {
+        self.function_table.iter()
+    }
+
+    pub fn function(&self, module_id: &str, symbol: &str) -> Option<&FunctionProfile> {
+        let function = (Name::get(module_id)?, Name::get(symbol)?);
+        self.function_table.get(&function)
+    }
+
+    // invocation count of each native
+    pub fn natives(&self) -> impl Iterator<Item = (&NativeId, u64)> {
+        self.native_table.iter().map(|(id, count)| (id, *count))
+    }
+
+    pub fn write_folded<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        let mut sample_list: Vec<_> = self.sample_table.iter().collect();
+        sample_list.sort();
+        for (stack, count) in sample_list {
+            let stack: Vec<_> = stack
+                .iter()
+                .map(|(module_id, symbol)| format!("{module_id}:{symbol}"))
+                .collect();
+            writeln!(writer, "{} {count}", stack.join(";"))?;
+        }
+        Ok(())
+    }
+
+    pub(crate) fn sample_due(&self) -> bool {
+        self.n_step.is_multiple_of(self.sample_interval.get())
+    }
+
+    // `backtrace` is present when `sample_due`
+    pub(crate) fn record_step(
+        &mut self,
+        entry: &TraceEntry,
+        time: Duration,
+        backtrace: Option<Vec<TraceEntry>>,
+    ) {
+        self.n_step += 1;
+        let profile = self.function_table.entry(function(entry)).or_default();
+        profile.n_instruction += 1;
+        profile.time += time;
+        if let Some(backtrace) = backtrace {
+            let stack = backtrace.iter().rev().map(function).collect();
+            *self.sample_table.entry(stack).or_default() += 1;
+        }
+    }
+
+    pub(crate) fn record_native(&mut self, native_id: &str) {
+        match self.native_table.get_mut(native_id) {
+            Some(count) => *count += 1,
+            None => {
+                self.native_table.insert(native_id.to_string(), 1);
+            }
+        }
+    }
+}
+
+fn function(entry: &TraceEntry) -> Function {
+    let symbol = entry.symbol.unwrap_or_else(|| Name::new("?"));
+    (entry.module_id, symbol)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::collector::{callee_interpreter, TestCollector};
+
+    #[test]
+    fn profile_calls() {
+        let mut interp = callee_interpreter(
+            "
+                .module main
+                .symbol start
+                    operate 0 push_callee
+                    call 0
+                    operate 0 push_callee
+                    call 0
+                    return 0
+                .symbol callee
+                    operate 0 push_callee
+                    return 0
+            ",
+        );
+        interp.set_profiler(Some(Profiler::new(NonZeroU64::new(1).unwrap())));
+        let mut collector = TestCollector::default();
+        while interp.has_step() {
+            interp.step(&mut collector).unwrap();
+        }
+
+        let profiler = interp.set_profiler(None).unwrap();
+        assert_eq!(profiler.function("main", "start").unwrap().n_instruction, 5);
+        assert_eq!(
+            profiler.function("main", "callee").unwrap().n_instruction,
+            4
+        );
+        assert_eq!(profiler.functions().count(), 2);
+        assert_eq!(
+            profiler.natives().collect::<Vec<_>>(),
+            [(&String::from("push_callee"), 4)]
+        );
+        let mut folded = Vec::new();
+        profiler.write_folded(&mut folded).unwrap();
+        assert_eq!(
+            String::from_utf8(folded).unwrap(),
+            "main:start 5\nmain:start;main:callee 4\n"
+        );
+    }
+}
@@ -1,8 +1,15 @@
-use crate::collector::{Address, Collector, Owned, Shared};
-use crate::interpreter::{ByteCode, Interpreter, Module, ModuleId};
-use crate::objects::{Closure, Dispatch, Pending, Ready};
+use crate::collector::{Address, Collector, CollectorId, Owned, Shared};
+use crate::interpreter::{
+    ByteCode, Continuation, Interpreter, InterpreterError, Module, RunStatus, TraceEntry,
+};
+use crate::name::Name;
+use crate::objects::{Dispatch, Pending, Ready};
 use crate::portal::{Portal, Task};
 use crate::TaskId;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+use std::num::NonZeroUsize;
 use std::sync::Arc;
 use std::thread::current;
 
@@ -10,79 +17,189 @@ pub struct Runner {
     interp: Interpreter,
     portal: Arc<Portal>,
     collector: Arc<Collector>,
+    fuel: NonZeroUsize, // instructions stepped for a task before it is preempted
+    start: Dispatch,
+    continuation_table: HashMap<TaskId, Continuation>, // of preempted and suspended tasks
 }
 
+pub const DEFAULT_FUEL: NonZeroUsize = NonZeroUsize::new(10000).unwrap();
+
 pub trait CollectorInterface {
     fn inspect(&self, address: Address) -> Shared;
     fn replace(&mut self, address: Address, owned: Owned) -> Owned;
     fn allocate(&mut self, handle: Owned) -> Address;
+    // constants cached by interpreter are only valid with the collector of this id
+    fn collector_id(&self) -> CollectorId;
+    // never collected and visible to every task. collectors without per-task heaps may
+    // keep it along with other objects
+    fn allocate_constant(&mut self, owned: Owned) -> Address {
+        self.allocate(owned)
+    }
 }
 
 impl Runner {
     pub fn new(portal: Arc<Portal>, collector: Arc<Collector>) -> Self {
+        Self::with_fuel(portal, collector, DEFAULT_FUEL)
+    }
+
+    // nonzero, as a task never advances with no fuel, preempted on every poll
+    pub fn with_fuel(portal: Arc<Portal>, collector: Arc<Collector>, fuel: NonZeroUsize) -> Self {
+        let start = Dispatch {
+            module_id: Name::new("//task.toplevel"),
+            symbol: Name::new("(start)"),
+        };
         let mut interp = Interpreter::new();
-        interp.load_module(Module {
-            id: Self::module_id(),
-            symbol_table: [(Self::start_symbol(), 0)].into_iter().collect(),
-            // TODO
-            program: vec![
-                ByteCode::AssertFloating(1),
-                ByteCode::Operate(1, Box::new(Closure::operate_apply)),
-                ByteCode::Call(1),
-                ByteCode::PackFloating(1),
-                // ready flag, capture pack, extracted result, updated task
-                // ByteCode::Operate(3, Box::new(Closure::operate_poll)),
-                ByteCode::Copy(4),
-                ByteCode::Copy(4),
-                ByteCode::Return(3), // (order in result list) ready flag, updated task, extracted result
-            ],
-        });
+        interp
+            .load_module(Module {
+                id: start.module_id,
+                symbol_table: [(start.symbol, 0)].into_iter().collect(),
+                program: vec![
+                    // task
+                    ByteCode::AssertFloating(1),
+                    // capture pack, dispatch, task
+                    ByteCode::Operate(1, "closure.apply".into()),
+                    // dispatch, capture pack | dispatch, task
+                    ByteCode::Copy(2),
+                    // result, capture pack | dispatch, task
+                    ByteCode::Call(1),
+                    ByteCode::AssertFloating(2),
+                    // task, result, capture pack
+                    ByteCode::Copy(4),
+                    // capture pack, task, result
+                    ByteCode::Copy(3),
+                    // capture pack, task*, result
+                    ByteCode::Operate(2, "closure.capture".into()),
+                    // result
+                    ByteCode::Copy(3),
+                    ByteCode::Return(1),
+                ],
+                ..Default::default()
+            })
+            .unwrap();
         Self {
             interp,
             portal,
             collector,
+            fuel,
+            start,
+            continuation_table: Default::default(),
         }
     }
 
-    fn module_id() -> ModuleId {
-        String::from("//task.toplevel")
-    }
-
-    fn start_symbol() -> String {
-        String::from("(start)")
+    // for loading task modules and registering their natives
+    pub fn interp_mut(&mut self) -> &mut Interpreter {
+        &mut self.interp
     }
 
-    pub fn poll_one(&mut self) {
+    pub fn poll_one(&mut self) -> Result<(), TaskFailure> {
         let task = self.portal.fetch(current().id());
-        self.collector.spawn(task.0);
-        self.interp.push_variable(task.1);
-        self.interp.push_call(
-            Dispatch {
-                module_id: Self::module_id(),
-                symbol: Self::start_symbol(),
-            },
-            0,
-        );
-        while self.interp.has_step() {
-            self.interp.step(&mut TaskCollector {
-                collector: &*self.collector,
-                task_id: task.0,
-            });
-        }
-        let result_list = self.interp.reset();
-        assert_eq!(result_list.len(), 1);
-        let result = self.collector.inspect(task.0, result_list[0]);
+        let result_list = match self.poll_task(task) {
+            Ok(Polled::Finished(result_list)) => result_list,
+            Ok(Polled::Preempted) => {
+                self.portal.preempt(current().id(), task);
+                return Ok(());
+            }
+            Ok(Polled::Suspended) => {
+                self.portal.suspend_frame(current().id(), task);
+                return Ok(());
+            }
+            Err(error) => {
+                self.interp.abort();
+                return Err(self.fail(task, FailureKind::Interpreter(Box::new(error))));
+            }
+        };
+        let result = match &*result_list {
+            [result] => self.collector.inspect(task.0, *result),
+            _ => return Err(self.fail(task, FailureKind::InvalidPoll)),
+        };
         if result.as_ref().is::<Pending>() {
             self.portal.suspend(current().id(), task);
         } else {
-            let result: &Ready = result.as_ref().downcast_ref().unwrap();
-            let result = result.0;
+            let Some(result) = result.as_ref().downcast_ref::<Ready>() else {
+                return Err(self.fail(task, FailureKind::InvalidPoll));
+            };
+            let _result = result.0;
             // TODO
             self.collector.join(task.0);
         }
+        Ok(())
+    }
+
+    fn poll_task(&mut self, task: Task) -> Result<Polled, InterpreterError> {
+        if let Some(continuation) = self.continuation_table.remove(&task.0) {
+            self.interp.resume(continuation);
+        } else {
+            self.collector.spawn(task.0);
+            self.interp.push_variable(task.1);
+            self.interp.push_call(self.start, 0)?;
+        }
+        let mut collector = TaskCollector {
+            collector: &self.collector,
+            task_id: task.0,
+        };
+        self.interp
+            .set_waker(Some(self.portal.frame_waker(current().id(), task)));
+        let status = self.interp.run(&mut collector, self.fuel.get());
+        self.interp.set_waker(None);
+        if !matches!(status, Ok(RunStatus::Suspended)) {
+            self.portal.retire_frame_waker(current().id(), task);
+        }
+        let polled = match status? {
+            RunStatus::Finished => return Ok(Polled::Finished(self.interp.reset())),
+            RunStatus::OutOfFuel => Polled::Preempted,
+            RunStatus::Suspended => Polled::Suspended,
+        };
+        self.continuation_table
+            .insert(task.0, self.interp.suspend());
+        Ok(polled)
+    }
+
+    fn fail(&self, task: Task, kind: FailureKind) -> TaskFailure {
+        self.collector.join(task.0);
+        TaskFailure { task, kind }
+    }
+}
+
+enum Polled {
+    Finished(Vec<Address>),
+    Preempted, // out of fuel before finishing the poll
+    Suspended, // by native, until woken
+}
+
+#[derive(Debug)]
+pub struct TaskFailure {
+    pub task: Task,
+    pub kind: FailureKind,
+}
+
+#[derive(Debug)]
+pub enum FailureKind {
+    Interpreter(Box<InterpreterError>),
+    InvalidPoll, // task closure returned neither Ready nor Pending
+}
+
+impl TaskFailure {
+    // guest frames at the point of failure, innermost first
+    pub fn backtrace(&self) -> &[TraceEntry] {
+        match &self.kind {
+            FailureKind::Interpreter(error) => &error.backtrace,
+            FailureKind::InvalidPoll => &[],
+        }
+    }
+}
+
+impl Display for TaskFailure {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "task {} failed: ", self.task.0)?;
+        match &self.kind {
+            FailureKind::Interpreter(error) => write!(f, "{error}"),
+            FailureKind::InvalidPoll => write!(f, "invalid poll result"),
+        }
     }
 }
 
+impl Error for TaskFailure {}
+
 struct TaskCollector<'a> {
     collector: &'a Collector,
     task_id: TaskId,
@@ -97,15 +214,186 @@ impl<'a> CollectorInterface for TaskCollector<'a> {
     fn replace(&mut self, address: Address, owned: Owned) -> Owned {
         self.collector.replace_owned(address, owned)
     }
+    fn allocate_constant(&mut self, owned: Owned) -> Address {
+        self.collector.allocate_constant(owned)
+    }
+    fn collector_id(&self) -> CollectorId {
+        self.collector.id()
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::assembler::assemble;
+    use crate::interpreter::ErrorKind;
+    use crate::native::NativeRegistry;
+    use crate::objects::Closure;
+    use std::sync::Mutex;
+
+    // task closure polling `body` once and then ready
+    const POLL: &str = "
+        .module main
+        .export poll
+        .constant dispatch main body
+        .symbol poll 1 2
+            unpack
+            load_constant 0
+            call 0
+            pack_floating 0
+            operate 1 ready.new
+            pack_floating 2
+            copy 2
+            return 2
+    ";
+
+    fn runner(
+        fuel: NonZeroUsize,
+        body: &str,
+        register: impl FnOnce(&mut NativeRegistry),
+    ) -> (Runner, Task) {
+        let mut portal = Portal::new();
+        portal.add_peer(current());
+        let portal = Arc::new(portal);
+        let collector = Arc::new(Collector::new());
+        let mut runner = Runner::with_fuel(portal.clone(), collector.clone(), fuel);
+        register(runner.interp_mut().registry_mut());
+        let module = assemble(&format!("{POLL}{body}")).unwrap();
+        runner.interp_mut().load_module(module).unwrap();
+        // the first task id, whose heap holds the closure ahead of the first poll
+        collector.spawn(0);
+        let closure = Closure {
+            dispatch: Dispatch {
+                module_id: Name::new("main"),
+                symbol: Name::new("poll"),
+            },
+            capture_list: Vec::new(),
+        };
+        let closure = collector.allocate(0, closure.into());
+        let task = portal.spawn(current().id(), closure);
+        assert_eq!(task.0, 0);
+        (runner, task)
+    }
 
     #[test]
     fn no_task() {
         // let mut runner = Runner::new();
         // assert!(runner.prepare_task().is_none());
     }
+
+    #[test]
+    fn preempt_and_requeue() {
+        let (mut runner, task) = runner(
+            NonZeroUsize::new(3).unwrap(),
+            ".symbol body\nreturn 0",
+            |_| {},
+        );
+        runner.poll_one().unwrap();
+        assert!(runner.continuation_table.contains_key(&task.0));
+        // fetched again from the preempted queue, until finished with `Ready`
+        let mut n_poll = 1;
+        while runner.continuation_table.contains_key(&task.0) {
+            runner.poll_one().unwrap();
+            n_poll += 1;
+        }
+        assert_eq!(n_poll, 7);
+        assert!(!runner.interp.has_step());
+    }
+
+    #[test]
+    fn failure_backtrace() {
+        let (mut runner, task) = runner(
+            DEFAULT_FUEL,
+            ".symbol body\nassert_floating 1\nreturn 0",
+            |_| {},
+        );
+        let failure = runner.poll_one().unwrap_err();
+        assert_eq!(failure.task, task);
+        let entry = |module_id, symbol, offset| TraceEntry {
+            module_id: Name::new(module_id),
+            symbol: Some(Name::new(symbol)),
+            offset,
+        };
+        assert_eq!(
+            failure.backtrace(),
+            [
+                entry("main", "body", 8),
+                entry("main", "poll", 2),
+                entry("//task.toplevel", "(start)", 3),
+            ]
+        );
+        // the next task starts from a clean interpreter
+        assert!(!runner.interp.has_step());
+    }
+
+    #[test]
+    fn uncaught_throw() {
+        let body = ".symbol body\nload_constant 0\nthrow";
+        let (mut runner, task) = runner(DEFAULT_FUEL, body, |_| {});
+        let failure = runner.poll_one().unwrap_err();
+        assert_eq!(failure.task, task);
+        let FailureKind::Interpreter(error) = failure.kind else {
+            panic!("unexpected failure {failure}")
+        };
+        assert!(matches!(error.kind, ErrorKind::Uncaught(_)));
+        assert_eq!(error.backtrace[0].symbol, Some(Name::new("body")));
+        assert!(!runner.interp.has_step());
+    }
+
+    #[test]
+    fn resume_generator() {
+        let body = "
+            .constant dispatch main once
+            .symbol body
+                load_constant 1
+                operate 1 generator.new
+                copy 1
+                resume 0
+                jump_unless fail
+                copy 1
+                resume 0
+                jump_if fail
+                return 0
+            fail:
+                load_constant 1
+                throw
+            .symbol once
+                yield 0
+                return 0
+        ";
+        let (mut runner, _) = runner(DEFAULT_FUEL, body, |_| {});
+        // generator state is replaced in the task heap on every resume and yield
+        runner.poll_one().unwrap();
+        assert!(runner.continuation_table.is_empty());
+        assert!(!runner.interp.has_step());
+    }
+
+    #[test]
+    fn suspend_and_wake() {
+        for wake_early in [false, true] {
+            let waker_slot = Arc::new(Mutex::new(None));
+            let native_slot = waker_slot.clone();
+            let body = ".symbol body\noperate 0 suspend\nreturn 0";
+            let (mut runner, task) = runner(DEFAULT_FUEL, body, |registry| {
+                registry.register("suspend", 0, move |context| {
+                    let waker = context.suspend().unwrap();
+                    // before the runner parks the task
+                    if wake_early {
+                        waker()
+                    } else {
+                        *native_slot.lock().unwrap() = Some(waker);
+                    }
+                })
+            });
+            runner.poll_one().unwrap();
+            assert!(runner.continuation_table.contains_key(&task.0));
+            if let Some(waker) = waker_slot.lock().unwrap().take() {
+                waker();
+            }
+            // resumed in the middle of `body`
+            runner.poll_one().unwrap();
+            assert!(runner.continuation_table.is_empty());
+            assert!(!runner.interp.has_step());
+        }
+    }
 }
@@ -0,0 +1,188 @@
+// execution trace in JSON Lines, enabled by `Interpreter::set_tracer`
+//
+//     {"module":"main","offset":1,"kind":"copy","stack_depth":1,"frame_depth":1,
+//      "popped":[],"pushed":[[0,1]]}
+//
+// one line per step, depths are taken before the step. addresses are
+// [task id, index] pairs, popped ones listed bottom first. a faulted step
+// additionally has an "error" message and leaves stacks untouched
+use crate::collector::Address;
+use crate::interpreter::{InterpreterError, ModuleId};
+use std::fmt::Write as _;
+use std::io::{self, Write};
+
+pub struct Tracer {
+    writer: Box<dyn Write + Send>,
+    error: Option<io::Error>, // first failed write, following steps are not recorded
+}
+
+pub(crate) struct StepRecord<'a> {
+    pub pointer: &'a (ModuleId, usize),
+    pub kind: &'static str,
+    pub stack_before: &'a [Address],
+    pub stack_after: &'a [Address],
+    pub frame_depth: usize,
+    pub error: Option<&'a InterpreterError>,
+}
+
+impl Tracer {
+    pub fn new(writer: impl Write + Send + 'static) -> Self {
+        Self {
+            writer: Box::new(writer),
+            error: None,
+        }
+    }
+
+    // flush and report the first write error if any
+    pub fn finish(mut self) -> io::Result<()> {
+        if let Some(error) = self.error.take() {
+            return Err(error);
+        }
+        self.writer.flush()
+    }
+
+    pub(crate) fn record(&mut self, step: StepRecord<'_>) {
+        if self.error.is_some() {
+            return;
+        }
+        let common = step
+            .stack_before
+            .iter()
+            .zip(step.stack_after)
+            .take_while(|(before, after)| before == after)
+            .count();
+        let mut line = String::from("{");
+        write!(line, "\"module\":{}", quote(&step.pointer.0)).unwrap();
+        write!(line, ",\"offset\":{}", step.pointer.1).unwrap();
+        write!(line, ",\"kind\":\"{}\"", step.kind).unwrap();
+        write!(line, ",\"stack_depth\":{}", step.stack_before.len()).unwrap();
+        write!(line, ",\"frame_depth\":{}", step.frame_depth).unwrap();
+        write!(
+            line,
+            ",\"popped\":{}",
+            addresses(&step.stack_before[common..])
+        )
+        .unwrap();
+        write!(
+            line,
+            ",\"pushed\":{}",
+            addresses(&step.stack_after[common..])
+        )
+        .unwrap();
+        if let Some(error) = step.error {
+            write!(line, ",\"error\":{}", quote(&error.kind.to_string())).unwrap();
+        }
+        line.push('}');
+        if let Err(error) = writeln!(self.writer, "{line}") {
+            self.error = Some(error);
+        }
+    }
+}
+
+fn addresses(address_list: &[Address]) -> String {
+    let address_list: Vec<_> = address_list
+        .iter()
+        .map(|(task_id, index)| format!("[{task_id},{index}]"))
+        .collect();
+    format!("[{}]", address_list.join(","))
+}
+
+fn quote(text: &str) -> String {
+    let mut quoted = String::from("\"");
+    for char in text.chars() {
+        match char {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            char if char.is_control() => write!(quoted, "\\u{:04x}", char as u32).unwrap(),
+            char => quoted.push(char),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assembler::assemble;
+    use crate::collector::TestCollector;
+    use crate::interpreter::Interpreter;
+    use crate::name::Name;
+    use crate::native::NativeRegistry;
+    use crate::objects::{Dispatch, List};
+
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Clone, Default)]
+    struct Buffer(Arc<Mutex<Vec<u8>>>);
+    impl Write for Buffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn trace_steps() {
+        let mut registry = NativeRegistry::new();
+        registry.register("push_list", 1, |context| {
+            let list = context.allocate(List(Vec::new()).into());
+            context.push_result(list);
+        });
+        let module = assemble(
+            "
+                .module main
+                .symbol start
+                    operate 0 push_list
+                    copy 1
+                    unpack
+                    return 1
+                ",
+        )
+        .unwrap();
+        let mut interp = Interpreter::with_registry(registry);
+        interp.load_module(module).unwrap();
+        let buffer = Buffer::default();
+        interp.set_tracer(Some(Tracer::new(buffer.clone())));
+        interp
+            .push_call(
+                Dispatch {
+                    module_id: Name::new("main"),
+                    symbol: Name::new("start"),
+                },
+                0,
+            )
+            .unwrap();
+        let mut collector = TestCollector::default();
+        while interp.has_step() {
+            interp.step(&mut collector).unwrap();
+        }
+        interp.set_tracer(None).unwrap().finish().unwrap();
+
+        let trace = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        let line = |offset, kind, stack_depth, popped, pushed| {
+            format!(
+                "{{\"module\":\"main\",\"offset\":{offset},\"kind\":\"{kind}\",\
+                \"stack_depth\":{stack_depth},\"frame_depth\":1,\
+                \"popped\":{popped},\"pushed\":{pushed}}}"
+            )
+        };
+        assert_eq!(
+            trace.lines().collect::<Vec<_>>(),
+            [
+                line(0, "operate", 0, "[]", "[[0,1]]"),
+                line(1, "copy", 1, "[]", "[[0,1]]"),
+                line(2, "unpack", 2, "[[0,1]]", "[]"),
+                line(3, "return", 1, "[]", "[]"),
+            ]
+        );
+    }
+
+    #[test]
+    fn quote_string() {
+        assert_eq!(quote("a\"b\\c\n\t"), "\"a\\\"b\\\\c\\n\\u0009\"");
+    }
+}
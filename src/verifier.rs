@@ -0,0 +1,702 @@
+// static checks on a module before it is loaded
+//
+// jump targets, symbol offsets and constant indices are verified: a loaded module
+// never refers outside its program or constant pool
+//
+// stack depth is tracked per instruction as lower bounds relative to the frame
+// entry, and an instruction is rejected when the bound on any path is below the
+// depth it takes, so a loaded module never underflows its frame. arguments of
+// symbols without signature and results of unknown callees are only known to be
+// at least zero, and are asserted with `AssertFloating` before use. a bound is exact
+// when some path reaches the instruction with exactly that depth, e.g. after
+// `AssertFloating` or `PackFloating`, which rejects an `AssertFloating` failing for
+// sure
+//
+// declared signatures give the exact depth on entry, and on return from calls whose
+// dispatch is a constant of the same module loaded right before the call
+use crate::interpreter::{jump_target, ByteCode, Constant, Module, ModuleId, Signature};
+use crate::name::Name;
+use crate::native::NativeRegistry;
+use std::collections::HashSet;
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VerifyError {
+    pub module_id: ModuleId,
+    pub offset: usize,
+    pub kind: VerifyErrorKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VerifyErrorKind {
+    SymbolOutOfProgram(Name),
+    ConstantOutOfPool(u16),
+    JumpOutOfProgram(isize),
+    FallOffEnd,
+    EmptyRotate,
+    StackUnderflow,
+    FloatingMismatch { expected: usize, actual: usize },
+    ArgumentMismatch { expected: usize, actual: usize },
+    ReturnMismatch { expected: usize, actual: usize },
+}
+
+impl Display for VerifyError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}: ", self.module_id, self.offset)?;
+        match &self.kind {
+            VerifyErrorKind::SymbolOutOfProgram(symbol) => {
+                write!(f, "symbol {symbol} out of program")
+            }
+            VerifyErrorKind::ConstantOutOfPool(index) => {
+                write!(f, "constant {index} out of pool")
+            }
+            VerifyErrorKind::JumpOutOfProgram(target) => {
+                write!(f, "jump target {target} out of program")
+            }
+            VerifyErrorKind::FallOffEnd => write!(f, "execution falls off the end of program"),
+            VerifyErrorKind::EmptyRotate => write!(f, "rotate of no variable"),
+            VerifyErrorKind::StackUnderflow => write!(f, "variable stack underflow"),
+            VerifyErrorKind::FloatingMismatch { expected, actual } => {
+                write!(f, "expect {expected} floating variables, found {actual}")
+            }
+            VerifyErrorKind::ArgumentMismatch { expected, actual } => {
+                write!(f, "expect {expected} arguments, called with {actual}")
+            }
+            VerifyErrorKind::ReturnMismatch { expected, actual } => {
+                write!(f, "expect {expected} results, returned {actual}")
+            }
+        }
+    }
+}
+
+impl Error for VerifyError {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Bound {
+    value: usize,
+    exact: bool,
+}
+
+impl Bound {
+    fn exact(value: usize) -> Self {
+        Self { value, exact: true }
+    }
+
+    fn at_least(value: usize) -> Self {
+        Self {
+            value,
+            exact: false,
+        }
+    }
+
+    fn add(self, other: Self) -> Self {
+        Self {
+            value: self.value + other.value,
+            exact: self.exact && other.exact,
+        }
+    }
+
+    fn below(self, expected: usize) -> bool {
+        self.value < expected
+    }
+}
+
+// variable stack depth relative to the stack size on frame entry
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Depth {
+    base: Bound, // current frame stack size
+    floating: Bound,
+}
+
+impl Depth {
+    fn entry(signature: Option<&Signature>) -> Self {
+        Self {
+            base: Bound::exact(0),
+            floating: match signature {
+                Some(signature) => Bound::exact(signature.n_parameter as usize),
+                None => Bound::at_least(0),
+            },
+        }
+    }
+
+    fn is_exact(self) -> bool {
+        self.base.exact && self.floating.exact
+    }
+
+    fn not_above(self, other: Self) -> bool {
+        self.base.value <= other.base.value && self.floating.value <= other.floating.value
+    }
+
+    // exactness of both bounds must come from the same path
+    fn merge(self, other: Self) -> Self {
+        match (self.not_above(other), other.not_above(self)) {
+            (true, true) if other.is_exact() => other,
+            (true, _) => self,
+            (false, true) => other,
+            (false, false) => Self {
+                base: Bound::at_least(self.base.value.min(other.base.value)),
+                floating: Bound::at_least(self.floating.value.min(other.floating.value)),
+            },
+        }
+    }
+
+    fn height(self) -> Bound {
+        self.base.add(self.floating)
+    }
+
+    // underflow is checked separately
+    fn pop(self, n: usize) -> Self {
+        Self {
+            floating: Bound {
+                value: self.floating.value.saturating_sub(n),
+                ..self.floating
+            },
+            ..self
+        }
+    }
+
+    fn push(self, n: usize) -> Self {
+        Self {
+            floating: self.floating.add(Bound::exact(n)),
+            ..self
+        }
+    }
+}
+
+pub fn verify(module: &Module, registry: &NativeRegistry) -> Result<(), VerifyError> {
+    let error = |offset, kind| VerifyError {
+        module_id: module.id,
+        offset,
+        kind,
+    };
+    let program = &module.program;
+
+    let mut entry_list: Vec<_> = module.symbol_table.iter().collect();
+    entry_list.sort_by_key(|(symbol, offset)| (**offset, *symbol));
+    for (symbol, offset) in &entry_list {
+        if **offset >= program.len() {
+            return Err(error(
+                **offset,
+                VerifyErrorKind::SymbolOutOfProgram(**symbol),
+            ));
+        }
+    }
+    for (offset, instruction) in program.iter().enumerate() {
+        if let Some(target) = jump_target(offset, instruction) {
+            if target < 0 || target as usize >= program.len() {
+                return Err(error(offset, VerifyErrorKind::JumpOutOfProgram(target)));
+            }
+        }
+    }
+
+    // instructions that may be reached other than from the previous one
+    let mut target_set: HashSet<_> = entry_list.iter().map(|(_, offset)| **offset).collect();
+    for (offset, instruction) in program.iter().enumerate() {
+        target_set.extend(jump_target(offset, instruction).map(|target| target as usize));
+    }
+    let known_callee = |offset: usize| {
+        if offset == 0 || target_set.contains(&offset) {
+            return None;
+        }
+        let ByteCode::LoadConstant(index) = &program[offset - 1] else {
+            return None;
+        };
+        match module.constant_list.get(*index as usize) {
+            Some(Constant::Dispatch(dispatch)) if dispatch.module_id == module.id => {
+                module.signature_table.get(&dispatch.symbol)
+            }
+            _ => None,
+        }
+    };
+
+    for (symbol, offset) in &entry_list {
+        if let Some(signature) = module.signature_table.get(*symbol) {
+            check_return(program, **offset, signature, &known_callee)
+                .map_err(|(offset, kind)| error(offset, kind))?;
+        }
+    }
+
+    let mut depth_list: Vec<Option<Depth>> = vec![None; program.len()];
+    let mut work_list = Vec::new();
+    for (symbol, offset) in entry_list.into_iter().rev() {
+        let depth = Depth::entry(module.signature_table.get(symbol));
+        depth_list[*offset] = Some(match depth_list[*offset] {
+            Some(previous) => previous.merge(depth),
+            None => depth,
+        });
+        work_list.push(*offset);
+    }
+    while let Some(offset) = work_list.pop() {
+        let depth = depth_list[offset].unwrap();
+        let underflow = |bound: Bound, expected| {
+            if bound.below(expected) {
+                Err(error(offset, VerifyErrorKind::StackUnderflow))
+            } else {
+                Ok(())
+            }
+        };
+        let mut successor_list = Vec::new();
+        let next = match &program[offset] {
+            ByteCode::Copy(n) => {
+                if *n == 0 {
+                    return Err(error(offset, VerifyErrorKind::StackUnderflow));
+                }
+                underflow(depth.height(), *n as usize)?;
+                Some(depth.push(1))
+            }
+            ByteCode::Operate(n_argument, native_id) => {
+                underflow(depth.height(), *n_argument as usize)?;
+                Some(match registry.get(native_id) {
+                    Some(native) => depth.push(native.n_result as usize),
+                    None => Depth {
+                        floating: Bound::at_least(depth.floating.value),
+                        ..depth
+                    },
+                })
+            }
+            ByteCode::Jump(_) => {
+                underflow(depth.height(), 1)?;
+                let target = jump_target(offset, &program[offset]).unwrap();
+                successor_list.push((target as usize, depth));
+                Some(depth)
+            }
+            ByteCode::Goto(_) => {
+                let target = jump_target(offset, &program[offset]).unwrap();
+                successor_list.push((target as usize, depth));
+                None
+            }
+            ByteCode::JumpIf(_) | ByteCode::JumpUnless(_) => {
+                underflow(depth.floating, 1)?;
+                let depth = depth.pop(1);
+                let target = jump_target(offset, &program[offset]).unwrap();
+                successor_list.push((target as usize, depth));
+                Some(depth)
+            }
+            ByteCode::Call(n_argument) | ByteCode::Resume(n_argument) => {
+                let n_consumed = *n_argument as usize + 1;
+                underflow(depth.floating, n_consumed)?;
+                let remain = Bound {
+                    value: depth.floating.value.saturating_sub(n_consumed),
+                    exact: depth.floating.exact,
+                };
+                let callee = match &program[offset] {
+                    ByteCode::Call(_) => known_callee(offset),
+                    _ => None,
+                };
+                if let Some(callee) = callee {
+                    if callee.n_parameter != *n_argument {
+                        return Err(error(
+                            offset,
+                            VerifyErrorKind::ArgumentMismatch {
+                                expected: callee.n_parameter as usize,
+                                actual: *n_argument as usize,
+                            },
+                        ));
+                    }
+                }
+                Some(Depth {
+                    base: depth.base.add(remain),
+                    floating: match (&program[offset], callee) {
+                        (_, Some(callee)) => Bound::exact(callee.n_result as usize),
+                        // the boolean telling whether the generator yielded
+                        (ByteCode::Resume(_), _) => Bound::at_least(1),
+                        _ => Bound::at_least(0),
+                    },
+                })
+            }
+            ByteCode::TailCall(n_argument) => {
+                underflow(depth.floating, *n_argument as usize + 1)?;
+                match known_callee(offset) {
+                    Some(callee) if callee.n_parameter != *n_argument => {
+                        return Err(error(
+                            offset,
+                            VerifyErrorKind::ArgumentMismatch {
+                                expected: callee.n_parameter as usize,
+                                actual: *n_argument as usize,
+                            },
+                        ))
+                    }
+                    _ => None,
+                }
+            }
+            ByteCode::Return(n_returned) => {
+                underflow(depth.floating, *n_returned as usize)?;
+                None
+            }
+            ByteCode::AssertFloating(n_floating) => {
+                let expected = *n_floating as usize;
+                let actual = depth.floating;
+                if actual.value > expected || (actual.exact && actual.value != expected) {
+                    return Err(error(
+                        offset,
+                        VerifyErrorKind::FloatingMismatch {
+                            expected,
+                            actual: actual.value,
+                        },
+                    ));
+                }
+                Some(Depth {
+                    floating: Bound::exact(expected),
+                    ..depth
+                })
+            }
+            ByteCode::PackFloating(n_destructed) => {
+                underflow(depth.floating, *n_destructed as usize)?;
+                Some(Depth {
+                    floating: Bound::exact(*n_destructed as usize + 1),
+                    ..depth
+                })
+            }
+            ByteCode::Unpack => {
+                underflow(depth.floating, 1)?;
+                Some(Depth {
+                    floating: Bound::at_least(depth.floating.value.saturating_sub(1)),
+                    ..depth
+                })
+            }
+            ByteCode::Try(_) => {
+                // variables floating on installing may be consumed before the throw, so
+                // the handler only gets the exception for sure
+                let handler = Depth {
+                    floating: Bound::at_least(1),
+                    ..depth
+                };
+                let target = jump_target(offset, &program[offset]).unwrap();
+                successor_list.push((target as usize, handler));
+                Some(depth)
+            }
+            ByteCode::LoadConstant(index) => {
+                if *index as usize >= module.constant_list.len() {
+                    return Err(error(offset, VerifyErrorKind::ConstantOutOfPool(*index)));
+                }
+                Some(depth.push(1))
+            }
+            ByteCode::LoadLocal(index) => {
+                underflow(depth.height(), *index as usize + 1)?;
+                Some(depth.push(1))
+            }
+            ByteCode::StoreLocal(index) => {
+                underflow(depth.floating, 1)?;
+                underflow(depth.height(), *index as usize + 2)?;
+                Some(depth.pop(1))
+            }
+            ByteCode::Pop => {
+                underflow(depth.floating, 1)?;
+                Some(depth.pop(1))
+            }
+            ByteCode::Swap => {
+                underflow(depth.floating, 2)?;
+                Some(depth)
+            }
+            ByteCode::Rotate(n) => {
+                if *n == 0 {
+                    return Err(error(offset, VerifyErrorKind::EmptyRotate));
+                }
+                underflow(depth.floating, *n as usize)?;
+                Some(depth)
+            }
+            ByteCode::EndTry => Some(depth),
+            ByteCode::Throw => {
+                underflow(depth.floating, 1)?;
+                None
+            }
+            ByteCode::Yield(n_yielded) => {
+                // the yielded variables are replaced by the ones passed to the next resume
+                underflow(depth.floating, *n_yielded as usize)?;
+                Some(Depth {
+                    floating: Bound::at_least(
+                        depth.floating.value.saturating_sub(*n_yielded as usize),
+                    ),
+                    ..depth
+                })
+            }
+        };
+        if let Some(depth) = next {
+            if offset + 1 == program.len() {
+                return Err(error(offset, VerifyErrorKind::FallOffEnd));
+            }
+            successor_list.push((offset + 1, depth));
+        }
+
+        for (successor, depth) in successor_list {
+            let merged = match depth_list[successor] {
+                Some(previous) => previous.merge(depth),
+                None => depth,
+            };
+            if depth_list[successor] != Some(merged) {
+                depth_list[successor] = Some(merged);
+                work_list.push(successor);
+            }
+        }
+    }
+    Ok(())
+}
+
+// every return reachable from the entry, including through tail calls to known callees,
+// must pass the declared number of results
+fn check_return<'m>(
+    program: &[ByteCode],
+    entry: usize,
+    signature: &Signature,
+    known_callee: &impl Fn(usize) -> Option<&'m Signature>,
+) -> Result<(), (usize, VerifyErrorKind)> {
+    let mismatch = |offset, actual: u8| {
+        Err((
+            offset,
+            VerifyErrorKind::ReturnMismatch {
+                expected: signature.n_result as usize,
+                actual: actual as usize,
+            },
+        ))
+    };
+    let mut visited = vec![false; program.len()];
+    let mut work_list = vec![entry];
+    while let Some(offset) = work_list.pop() {
+        if visited[offset] {
+            continue;
+        }
+        visited[offset] = true;
+        let instruction = &program[offset];
+        match instruction {
+            ByteCode::Return(n_returned) if *n_returned != signature.n_result => {
+                return mismatch(offset, *n_returned);
+            }
+            ByteCode::TailCall(_) => match known_callee(offset) {
+                Some(callee) if callee.n_result != signature.n_result => {
+                    return mismatch(offset, callee.n_result);
+                }
+                _ => {}
+            },
+            _ => {}
+        }
+        if let Some(target) = jump_target(offset, instruction) {
+            work_list.push(target as usize);
+        }
+        let terminal = matches!(
+            instruction,
+            ByteCode::Goto(_) | ByteCode::TailCall(_) | ByteCode::Return(_) | ByteCode::Throw
+        );
+        if !terminal && offset + 1 < program.len() {
+            work_list.push(offset + 1);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assembler::assemble;
+    use crate::name::Name;
+
+    fn check(source: &str) -> Result<(), VerifyErrorKind> {
+        let module = assemble(&format!(".module main\n.symbol start\n{source}")).unwrap();
+        verify(&module, &NativeRegistry::standard()).map_err(|error| error.kind)
+    }
+
+    #[test]
+    fn accept_well_formed() {
+        check(
+            "
+                assert_floating 1
+                operate 1 closure.apply
+                copy 2
+                call 1
+                assert_floating 2
+                copy 4
+                copy 3
+                operate 2 closure.capture
+                copy 3
+                return 1
+            ",
+        )
+        .unwrap();
+        // entry depth is unknown until asserted
+        check("assert_floating 2\ncopy 2\nunpack\nassert_floating 3\nreturn 1").unwrap();
+        // tail call does not fall through
+        check("assert_floating 2\ntail_call 1").unwrap();
+        // handler receives the exception
+        check("assert_floating 0\ntry handler\nend_try\nreturn 0\nhandler:\nreturn 1").unwrap();
+        // generator body and its consumer
+        check("assert_floating 1\nyield 1\nassert_floating 1\nreturn 1").unwrap();
+        check("assert_floating 2\nresume 1\njump_unless +0\nreturn 0").unwrap();
+        // resume pushes at least the boolean
+        check("assert_floating 1\nresume 0\nreturn 1").unwrap();
+        check(".constant false\nassert_floating 0\nload_constant 0\nreturn 1").unwrap();
+        check("assert_floating 2\nload_local 1\nstore_local 0\nswap\nrotate 2\npop\nreturn 1")
+            .unwrap();
+        // growing loop
+        check("assert_floating 1\nloop:\ncopy 1\njump loop\nreturn 0").unwrap();
+    }
+
+    #[test]
+    fn reject_malformed() {
+        assert_eq!(
+            check("jump +5\nreturn 0"),
+            Err(VerifyErrorKind::JumpOutOfProgram(6))
+        );
+        assert_eq!(
+            check("assert_floating 0\ncopy 1\nreturn 0"),
+            Err(VerifyErrorKind::StackUnderflow)
+        );
+        assert_eq!(
+            check("assert_floating 1\ncall 1\nreturn 0"),
+            Err(VerifyErrorKind::StackUnderflow)
+        );
+        assert_eq!(
+            check("pack_floating 0\nreturn 2"),
+            Err(VerifyErrorKind::StackUnderflow)
+        );
+        assert_eq!(
+            check("assert_floating 1\noperate 1 closure.apply\nassert_floating 2\nreturn 0"),
+            Err(VerifyErrorKind::FloatingMismatch {
+                expected: 2,
+                actual: 3
+            })
+        );
+        assert_eq!(
+            check("assert_floating 0\nunpack\nreturn 0"),
+            Err(VerifyErrorKind::StackUnderflow)
+        );
+        assert_eq!(
+            check("assert_floating 0\njump_if +0\nreturn 0"),
+            Err(VerifyErrorKind::StackUnderflow)
+        );
+        assert_eq!(
+            check("goto +0\npack_floating 0"),
+            Err(VerifyErrorKind::FallOffEnd)
+        );
+        assert_eq!(
+            check("assert_floating 1\ntail_call 1"),
+            Err(VerifyErrorKind::StackUnderflow)
+        );
+        assert_eq!(
+            check("assert_floating 0\nthrow"),
+            Err(VerifyErrorKind::StackUnderflow)
+        );
+        assert_eq!(
+            check("assert_floating 1\nresume 1\nreturn 0"),
+            Err(VerifyErrorKind::StackUnderflow)
+        );
+        assert_eq!(
+            check("assert_floating 0\nyield 1\nreturn 0"),
+            Err(VerifyErrorKind::StackUnderflow)
+        );
+        assert_eq!(check("end_try"), Err(VerifyErrorKind::FallOffEnd));
+        // unknown depth on entry and after calling an unknown callee
+        assert_eq!(
+            check("copy 1\nreturn 0"),
+            Err(VerifyErrorKind::StackUnderflow)
+        );
+        assert_eq!(
+            check("assert_floating 1\ncall 0\nreturn 1"),
+            Err(VerifyErrorKind::StackUnderflow)
+        );
+        // the handler may only get the exception
+        assert_eq!(
+            check("assert_floating 1\ntry handler\nend_try\nreturn 0\nhandler:\nreturn 2"),
+            Err(VerifyErrorKind::StackUnderflow)
+        );
+        assert_eq!(
+            check("assert_floating 1\nload_local 1\nreturn 0"),
+            Err(VerifyErrorKind::StackUnderflow)
+        );
+        assert_eq!(
+            check("assert_floating 1\nstore_local 0\nreturn 0"),
+            Err(VerifyErrorKind::StackUnderflow)
+        );
+        assert_eq!(
+            check("assert_floating 2\nrotate 3\nreturn 0"),
+            Err(VerifyErrorKind::StackUnderflow)
+        );
+        assert_eq!(
+            check(".constant true\nload_constant 1\nreturn 1"),
+            Err(VerifyErrorKind::ConstantOutOfPool(1))
+        );
+    }
+
+    #[test]
+    fn empty_rotate() {
+        let mut module = assemble(".module main\n.symbol start\nrotate 1\nreturn 0").unwrap();
+        module.program[0] = ByteCode::Rotate(0);
+        assert_eq!(
+            verify(&module, &NativeRegistry::standard()).map_err(|error| error.kind),
+            Err(VerifyErrorKind::EmptyRotate)
+        );
+    }
+
+    #[test]
+    fn underflow_on_one_path() {
+        assert_eq!(
+            check(
+                "
+                assert_floating 1
+                jump short
+                copy 1
+                short:
+                pack_floating 2
+                return 0
+                "
+            ),
+            Err(VerifyErrorKind::StackUnderflow)
+        );
+    }
+
+    #[test]
+    fn declared_signature() {
+        let check = |source: &str| {
+            let module = assemble(&format!(
+                ".module main\n.constant dispatch main inc\n.symbol inc 1 1\nreturn 1\n{source}"
+            ))
+            .unwrap();
+            verify(&module, &NativeRegistry::standard()).map_err(|error| error.kind)
+        };
+        check(".symbol start 1 1\ncopy 1\nload_constant 0\ncall 1\nreturn 1").unwrap();
+        check(".symbol start 1 1\nload_constant 0\ntail_call 1").unwrap();
+        // arguments are exact on entry, results are exact after the known call
+        assert_eq!(
+            check(".symbol start 0 1\nreturn 1"),
+            Err(VerifyErrorKind::StackUnderflow)
+        );
+        assert_eq!(
+            check(".symbol start\nassert_floating 1\nload_constant 0\ncall 1\nreturn 2"),
+            Err(VerifyErrorKind::StackUnderflow)
+        );
+        assert_eq!(
+            check(".symbol start\nassert_floating 2\nload_constant 0\ncall 2\nreturn 0"),
+            Err(VerifyErrorKind::ArgumentMismatch {
+                expected: 1,
+                actual: 2
+            })
+        );
+        assert_eq!(
+            check(".symbol start 2 1\njump +1\nreturn 1\nreturn 2"),
+            Err(VerifyErrorKind::ReturnMismatch {
+                expected: 1,
+                actual: 2
+            })
+        );
+        assert_eq!(
+            check(".symbol start 1 0\nload_constant 0\ntail_call 1"),
+            Err(VerifyErrorKind::ReturnMismatch {
+                expected: 0,
+                actual: 1
+            })
+        );
+    }
+
+    #[test]
+    fn symbol_out_of_program() {
+        let mut module = assemble(".module main\n.symbol start\nreturn 0").unwrap();
+        module.symbol_table.insert(Name::new("end"), 1);
+        assert_eq!(
+            verify(&module, &NativeRegistry::standard()),
+            Err(VerifyError {
+                module_id: Name::new("main"),
+                offset: 1,
+                kind: VerifyErrorKind::SymbolOutOfProgram(Name::new("end"))
+            })
+        );
+    }
+}